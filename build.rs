@@ -0,0 +1,9 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    // Exposed to config files as the default of the `${target_triple}` variable
+    println!(
+        "cargo:rustc-env=TARGET={}",
+        std::env::var("TARGET").expect("TARGET is set by cargo")
+    );
+}
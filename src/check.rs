@@ -0,0 +1,372 @@
+//! Linting of config files.
+//!
+//! [`check`] reports problems that do not prevent a config file from being
+//! loaded, but are most likely mistakes, e.g., patterns that never match
+//! any file, or a `default` part that does not exist.
+use crate::config::{expand_directory, merge_globs_and_regexes, Config, ConfigFile};
+use crate::error::Result;
+use crate::expr::validate_part_name;
+use crate::walk::{absolute, DirectoryMatcher, Matcher, Walker};
+use globset::{Glob, GlobSetBuilder};
+use regex::bytes::RegexSet;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Severity of a [`Diagnostic`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The config file is wrong, e.g., it refers to something that does
+    /// not exist.
+    Error,
+    /// The config file is valid, but probably does not do what is intended.
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A problem found in a config file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Name of the part the problem is in, if any.
+    pub part: Option<String>,
+    /// Key the problem is in, e.g., `"globs[1]"`, if any.
+    pub key: Option<String>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Return where the problem is, e.g., `"src.globs[1]"`, if known.
+    pub fn location(&self) -> Option<String> {
+        match (&self.part, &self.key) {
+            (Some(part), Some(key)) => Some(format!("{part}.{key}")),
+            (Some(part), None) => Some(part.clone()),
+            (None, Some(key)) => Some(key.clone()),
+            (None, None) => None,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.location() {
+            Some(location) => write!(f, "{}: {location}: {}", self.severity, self.message),
+            None => write!(f, "{}: {}", self.severity, self.message),
+        }
+    }
+}
+
+/// Return why a glob is suspicious, if it is.
+///
+/// Part globs are built without `literal_separator`, so `*` matches `/`,
+/// and `src/**.rs` matches the same files as `src/**/*.rs`. It is still
+/// reported, as it reads as a mistake.
+///
+/// # Examples
+///
+/// ```
+/// # use parts::check::suspicious_glob;
+/// assert!(suspicious_glob("src/**.rs").is_some());
+/// assert!(suspicious_glob("./src/*.rs").is_some());
+/// assert!(suspicious_glob("src/**/*.rs").is_none());
+/// ```
+pub fn suspicious_glob(glob: &str) -> Option<String> {
+    if glob.starts_with("./") {
+        return Some(format!(
+            "paths are matched without a leading `./`, write {:?} instead",
+            glob.trim_start_matches("./")
+        ));
+    }
+    if glob.starts_with('/') {
+        return Some("paths are matched relative to the directory of the config file".to_string());
+    }
+
+    let recursive = glob
+        .split('/')
+        .any(|component| component != "**" && component.contains("**"));
+
+    if recursive {
+        let fixed = glob
+            .split('/')
+            .map(|component| match component.strip_prefix("**") {
+                Some(rest) if !rest.is_empty() && !rest.contains("**") => format!("**/*{rest}"),
+                _ => component.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+        return Some(format!(
+            "`**` is only special as a whole path component, here it is the same as `*` \
+             (which also matches `/` in part globs), write {fixed:?} to make the intent clear"
+        ));
+    }
+
+    None
+}
+
+/// Number of files matched by each regex and glob of a list of patterns.
+struct Hits {
+    regexes: Vec<usize>,
+    globs: Vec<usize>,
+}
+
+impl Hits {
+    /// Count the matches of the patterns of a part, given files relative
+    /// to its root.
+    fn new(regexes: &RegexSet, globs: &[Glob], files: &[PathBuf]) -> Self {
+        let mut builder = GlobSetBuilder::new();
+
+        for glob in globs.iter() {
+            builder.add(glob.clone());
+        }
+
+        let globset = builder.build().expect("This cannot fail");
+        let mut hits = Hits {
+            regexes: vec![0; regexes.len()],
+            globs: vec![0; globs.len()],
+        };
+
+        for file in files.iter() {
+            let strl = file.to_string_lossy();
+
+            for index in regexes.matches(strl.as_bytes()).into_iter() {
+                hits.regexes[index] += 1;
+            }
+            for index in globset.matches(strl.as_ref()) {
+                hits.globs[index] += 1;
+            }
+        }
+
+        hits
+    }
+
+    /// Return the key, index and text of the patterns that matched no file.
+    fn unmatched<'a>(
+        &self,
+        (regexes_key, regexes): (&'static str, &'a RegexSet),
+        (globs_key, globs): (&'static str, &'a [Glob]),
+    ) -> Vec<(&'static str, usize, &'a str)> {
+        let regexes = regexes
+            .patterns()
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| self.regexes[*index] == 0)
+            .map(|(index, text)| (regexes_key, index, text.as_str()));
+        let globs = globs
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| self.globs[*index] == 0)
+            .map(|(index, glob)| (globs_key, index, glob.glob()));
+
+        regexes.chain(globs).collect()
+    }
+}
+
+/// Check a single part, given the files it may contain.
+fn check_part(
+    config_file: &ConfigFile,
+    name: &str,
+    config: &Config,
+    files: &[PathBuf],
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<()> {
+    let diagnostic = |severity, key: Option<String>, message| Diagnostic {
+        severity,
+        part: Some(name.to_string()),
+        key,
+        message,
+    };
+
+    if let Err(err) = validate_part_name(name) {
+        diagnostics.push(diagnostic(Severity::Warning, None, err.to_string()));
+    }
+
+    for (key, globs) in [
+        ("globs", &config.globs),
+        ("exclude_globs", &config.exclude_globs),
+    ] {
+        for (index, glob) in globs.iter().enumerate() {
+            if let Some(reason) = suspicious_glob(glob.glob()) {
+                diagnostics.push(diagnostic(
+                    Severity::Warning,
+                    Some(format!("{key}[{index}]")),
+                    format!("suspicious glob {:?}: {reason}", glob.glob()),
+                ));
+            }
+        }
+    }
+
+    let patterns = config
+        .directory
+        .iter()
+        .map(|directory| ("directory".to_string(), directory))
+        .chain(
+            config
+                .directories
+                .iter()
+                .enumerate()
+                .map(|(index, directory)| (format!("directories[{index}]"), directory)),
+        );
+
+    for (key, pattern) in patterns {
+        let expanded = expand_directory(&config.root, pattern);
+
+        if expanded.is_empty() {
+            diagnostics.push(diagnostic(
+                Severity::Warning,
+                Some(key),
+                format!("directory pattern {pattern:?} matches no directory"),
+            ));
+        } else if expanded.len() == 1 && !expanded[0].is_dir() {
+            diagnostics.push(diagnostic(
+                Severity::Error,
+                Some(key),
+                format!("directory {pattern:?} does not exist"),
+            ));
+        }
+    }
+
+    let directories: Vec<PathBuf> = config
+        .directories()
+        .into_iter()
+        .filter(|directory| directory.is_dir())
+        .collect();
+
+    if directories.is_empty() {
+        return Ok(());
+    }
+
+    let walker = config_file.walker(name)?;
+
+    if !files.iter().any(|file| walker.is_match(file)) {
+        diagnostics.push(diagnostic(
+            Severity::Warning,
+            None,
+            "part matches no file".to_string(),
+        ));
+    }
+
+    let own: Vec<PathBuf> = files
+        .iter()
+        .filter(|file| {
+            directories
+                .iter()
+                .any(|directory| absolute(file).starts_with(directory))
+        })
+        .map(|file| config.relative_path(file))
+        .collect();
+    let include = Hits::new(&config.regexes, &config.globs, &own);
+
+    for (key, index, text) in
+        include.unmatched(("regexes", &config.regexes), ("globs", &config.globs))
+    {
+        diagnostics.push(diagnostic(
+            Severity::Warning,
+            Some(format!("{key}[{index}]")),
+            format!("pattern {text:?} never matches any file"),
+        ));
+    }
+
+    // Exclude rules only matter for files that would otherwise be included
+    let mut include = vec![Matcher::Rules {
+        root: absolute(&config.root),
+        directories: directories
+            .iter()
+            .map(|directory| DirectoryMatcher::literal(directory))
+            .collect(),
+        include: merge_globs_and_regexes(config.globs.clone(), config.regexes.clone()),
+        exclude: RegexSet::empty(),
+    }];
+    for reference in config.include_parts.iter() {
+        include.push(config_file.matcher(reference)?);
+    }
+
+    let include = Matcher::Any(include);
+    let included: Vec<PathBuf> = files
+        .iter()
+        .filter(|file| include.is_match(file))
+        .map(|file| config.relative_path(file))
+        .collect();
+    let exclude = Hits::new(&config.exclude_regexes, &config.exclude_globs, &included);
+
+    for (key, index, text) in exclude.unmatched(
+        ("exclude_regexes", &config.exclude_regexes),
+        ("exclude_globs", &config.exclude_globs),
+    ) {
+        let message = if included.is_empty() {
+            format!("exclude pattern {text:?} is dead, as the part includes no file")
+        } else {
+            format!("exclude pattern {text:?} matches none of the included files")
+        };
+        diagnostics.push(diagnostic(
+            Severity::Warning,
+            Some(format!("{key}[{index}]")),
+            message,
+        ));
+    }
+
+    Ok(())
+}
+
+/// Check a config file for likely mistakes, and return the problems found.
+///
+/// Files are walked once per combination of the hidden and ignored files
+/// settings used by the parts, to find parts and patterns that match no
+/// file.
+pub fn check(config_file: &ConfigFile) -> Result<Vec<Diagnostic>> {
+    let mut diagnostics = vec![];
+
+    if let Some(default) = &config_file.default {
+        if !config_file.configs.contains_key(default) {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                part: None,
+                key: Some("default".to_string()),
+                message: match config_file.get_closest_match(default) {
+                    Some(name) => {
+                        format!("default part {default:?} does not exist (did you mean {name:?}?)")
+                    }
+                    None => format!("default part {default:?} does not exist"),
+                },
+            });
+        }
+    }
+
+    let mut groups: HashMap<(bool, bool), Vec<Matcher>> = HashMap::new();
+
+    for name in config_file.part_names() {
+        let walker = config_file.walker(name)?;
+        groups
+            .entry((walker.ignore_hidden(), walker.use_gitignore()))
+            .or_default()
+            .extend(walker.directories().into_iter().map(Matcher::directory));
+    }
+
+    let files: HashMap<(bool, bool), Vec<PathBuf>> = groups
+        .into_iter()
+        .map(|((ignore_hidden, use_gitignore), matchers)| {
+            let walker = Walker::new(Matcher::Any(matchers), ignore_hidden, use_gitignore);
+            ((ignore_hidden, use_gitignore), walker.collect())
+        })
+        .collect();
+
+    for name in config_file.part_names() {
+        let config = &config_file.configs[name];
+        check_part(
+            config_file,
+            name,
+            config,
+            &files[&(config.ignore_hidden, config.use_gitignore)],
+            &mut diagnostics,
+        )?;
+    }
+
+    Ok(diagnostics)
+}
@@ -1,23 +1,43 @@
+use crate::diagnostic::SourceError;
 use crate::error::{Error, Result};
-use globset::Glob;
+use crate::expr::Expr;
+use crate::interpolate::{interpolate_current, with_variables, Variables};
+use crate::walk::{
+    absolute, normalize, relative_to, walk_builder, DirectoryMatcher, Matcher, Walker,
+};
+use globset::{Glob, GlobBuilder, GlobMatcher};
 use itertools::Itertools;
 use lazy_static::lazy_static;
-use log::warn;
-use regex::bytes::{RegexSet, RegexSetBuilder};
+use log::{info, warn};
+use regex::bytes::{Regex, RegexSet, RegexSetBuilder};
+use serde::de::{DeserializeSeed, Error as _, IgnoredAny, MapAccess, Visitor};
 use serde::{Deserialize, Deserializer};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
 use termcolor::{ColorSpec, WriteColor};
 
 lazy_static! {
-    static ref POSSIBLE_CONFIG_PATHS: Vec<&'static str> = vec![
+    /// Default locations of config files, in order of precedence.
+    pub static ref POSSIBLE_CONFIG_PATHS: Vec<&'static str> = vec![
         "parts.toml",
         ".parts.toml",
+        "parts.yaml",
+        ".parts.yaml",
+        "parts.yml",
+        ".parts.yml",
+        "parts.json",
+        ".parts.json",
         "Cargo.toml:metadata.parts",
         "pyproject.toml:tool.parts",
+        "package.json:parts",
     ];
 }
 
 const SPLIT_PATH: char = ':';
 const SPLIT_KEYS: char = '.';
+/// Directory, next to a config file, containing fragments of it.
+const FRAGMENTS_DIR: &str = ".parts.d";
 
 /// Split a string into a path and a list of keys.
 ///
@@ -27,10 +47,10 @@ const SPLIT_KEYS: char = '.';
 /// # Examples
 ///
 /// ```
-/// # use crate::config::split_path_and_keys;
+/// # use parts::config::split_path_and_keys;
 /// let (path, keys) = split_path_and_keys(".parts.toml");
 /// assert_eq!(path, ".parts.toml");
-/// assert_eq!(keys, vec![]);
+/// assert!(keys.is_empty());
 ///
 /// let (path, keys) = split_path_and_keys("Cargo.toml:metadata.parts");
 /// assert_eq!(path, "Cargo.toml");
@@ -55,83 +75,780 @@ pub fn validate_config_file_value(value: &str) -> Result<String> {
     }
 }
 
+/// Pattern in which variables are interpolated, see [`crate::interpolate`].
+struct InterpolatedValue(String);
+
+impl<'de> Deserialize<'de> for InterpolatedValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let value = Cow::<str>::deserialize(deserializer)?;
+        interpolate_current(&value, false)
+            .map(InterpolatedValue)
+            .map_err(D::Error::custom)
+    }
+}
+
+/// Glob, deserialized on its own so that errors name the invalid pattern.
+///
+/// Errors are located where the format reports them, e.g., on the whole
+/// array for TOML, not on the pattern itself.
+struct GlobValue(Glob);
+
+impl<'de> Deserialize<'de> for GlobValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let InterpolatedValue(glob) = InterpolatedValue::deserialize(deserializer)?;
+        Glob::new(&glob).map(GlobValue).map_err(D::Error::custom)
+    }
+}
 
 fn deserialize_globs<'de, D: Deserializer<'de>>(
     deserializer: D,
 ) -> std::result::Result<Vec<Glob>, D::Error> {
-    use serde::de::Error;
-    use std::borrow::Cow;
-    let globs = <Vec<Cow<str>> as Deserialize>::deserialize(deserializer)?;
-    
-    globs.iter().map(|glob| Glob::new(glob).map_err(D::Error::custom)).collect()
+    let globs = Vec::<GlobValue>::deserialize(deserializer)?;
+    Ok(globs.into_iter().map(|GlobValue(glob)| glob).collect())
 }
 
-/// Try to parse a config file into a [`ConfigFile`] struct.
+/// Directory glob, deserialized on its own so that errors name the invalid
+/// pattern, located as for [`GlobValue`].
+struct DirectoryValue(String);
+
+impl<'de> Deserialize<'de> for DirectoryValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let directory = Cow::<str>::deserialize(deserializer)?;
+        let directory = interpolate_current(&directory, true).map_err(D::Error::custom)?;
+        directory_glob(&directory).map_err(D::Error::custom)?;
+        Ok(DirectoryValue(directory))
+    }
+}
+
+fn deserialize_directory<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Option<String>, D::Error> {
+    let directory = Option::<DirectoryValue>::deserialize(deserializer)?;
+    Ok(directory.map(|DirectoryValue(directory)| directory))
+}
+
+fn deserialize_directories<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vec<String>, D::Error> {
+    let directories = Vec::<DirectoryValue>::deserialize(deserializer)?;
+    Ok(directories
+        .into_iter()
+        .map(|DirectoryValue(directory)| directory)
+        .collect())
+}
+
+/// Compile a directory glob, in which `*` does not match `/`.
+pub fn directory_glob(pattern: &str) -> std::result::Result<GlobMatcher, globset::Error> {
+    let pattern = normalize(Path::new(pattern));
+    Ok(GlobBuilder::new(&pattern.to_string_lossy())
+        .literal_separator(true)
+        .build()?
+        .compile_matcher())
+}
+
+/// Return whether a path component contains glob metacharacters.
+pub fn is_glob(component: Component) -> bool {
+    component
+        .as_os_str()
+        .to_string_lossy()
+        .contains(['*', '?', '[', '{'])
+}
+
+/// Expand a directory pattern, relative to `root`, into the absolute
+/// paths of the directories it matches.
 ///
-/// If `keys` is not empty, it will first index the file as if it was
-/// a plain TOML document, then parse the appropriate nested table
-/// into a [`ConfigFile`].
-pub fn try_parse_config_file(path: &str, keys: Vec<&str>) -> Result<ConfigFile> {
-    let content = std::fs::read_to_string(&path)?;
-
-    if keys.is_empty() {
-        let config_file = toml::from_str(&content)?;
-        return Ok(config_file);
-    }
-    // If keys is not empty, we traverse the inner tables
-    let mut toml_document = content.parse::<toml::Value>()?;
-
-    for key in keys.into_iter() {
-        match toml_document {
-            toml::Value::Table(mut table) => {
-                toml_document = table.remove(key).ok_or(Error::KeysNotFound {
-                    keys: key.to_string(),
-                    path: path.to_string(),
-                })?;
+/// Patterns without glob metacharacters are returned as-is, even if the
+/// directory does not exist. Otherwise, the directories matching the
+/// pattern are searched from its longest literal prefix, e.g., `crates`
+/// for `crates/*/src`.
+pub fn expand_directory(root: &Path, pattern: &str) -> Vec<PathBuf> {
+    let directory = match DirectoryMatcher::new(root, pattern) {
+        Ok(directory) => directory,
+        Err(err) => {
+            warn!("{err}");
+            return vec![];
+        }
+    };
+    let glob = match &directory.glob {
+        Some(glob) => glob,
+        None => return vec![directory.prefix],
+    };
+    let root = absolute(root);
+    let path = PathBuf::from(glob.glob().glob());
+    let depth =
+        path.components().count() - relative_to(&directory.prefix, &root).components().count();
+    let recursive = path
+        .components()
+        .any(|component| component.as_os_str() == "**");
+    let mut directories: Vec<PathBuf> = ignore::WalkBuilder::new(&directory.prefix)
+        .standard_filters(false)
+        .max_depth(if recursive { None } else { Some(depth) })
+        .filter_entry(|de| de.file_name() != ".git")
+        .build()
+        .filter_map(|result| result.ok())
+        .filter(|de| de.file_type().is_some_and(|file_type| file_type.is_dir()))
+        .map(|de| absolute(de.path()))
+        .filter(|directory| glob.is_match(relative_to(directory, &root)))
+        .collect();
+
+    directories.sort();
+    directories
+}
+
+/// Regex, deserialized on its own so that errors name the invalid pattern,
+/// located as for [`GlobValue`].
+struct RegexValue(String);
+
+impl<'de> Deserialize<'de> for RegexValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let InterpolatedValue(regex) = InterpolatedValue::deserialize(deserializer)?;
+        Regex::new(&regex).map_err(D::Error::custom)?;
+        Ok(RegexValue(regex))
+    }
+}
+
+fn deserialize_regexes<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<RegexSet, D::Error> {
+    let regexes = Vec::<RegexValue>::deserialize(deserializer)?;
+    RegexSet::new(regexes.iter().map(|RegexValue(regex)| regex)).map_err(D::Error::custom)
+}
+
+/// Return the candidate closest to a (probably mistyped) name, if any is
+/// close enough.
+///
+/// Candidates are compared with the Damerau-Levenshtein distance, so that
+/// transposed characters count as a single edit, and the allowed distance
+/// grows with the length of the name.
+///
+/// # Examples
+///
+/// ```
+/// # use parts::config::closest_match;
+/// assert_eq!(closest_match("scr", ["src", "docs"]), Some("src"));
+/// assert_eq!(closest_match("tests", ["src", "docs"]), None);
+/// ```
+pub fn closest_match<'a, I: IntoIterator<Item = &'a str>>(
+    name: &str,
+    candidates: I,
+) -> Option<&'a str> {
+    candidates
+        .into_iter()
+        .map(|candidate| (strsim::damerau_levenshtein(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= std::cmp::max(1, name.chars().count() / 3))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Deserialize the [`ConfigFile`] nested under a path of keys in a
+/// document.
+///
+/// Unlike indexing a parsed document, this keeps track of where values
+/// are in the host file, so that errors can point at them.
+struct KeyPathSeed<'a> {
+    keys: &'a [&'a str],
+}
+
+/// Either the config file, or the first key that was not found, with the
+/// keys that are available instead.
+type KeyPathLookup = std::result::Result<ConfigFile, (String, Vec<String>)>;
+
+impl<'de> DeserializeSeed<'de> for KeyPathSeed<'_> {
+    type Value = KeyPathLookup;
+
+    fn deserialize<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<Self::Value, D::Error> {
+        if self.keys.is_empty() {
+            ConfigFile::deserialize(deserializer).map(Ok)
+        } else {
+            deserializer.deserialize_map(self)
+        }
+    }
+}
+
+impl<'de> Visitor<'de> for KeyPathSeed<'_> {
+    type Value = KeyPathLookup;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a table")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(
+        self,
+        mut map: A,
+    ) -> std::result::Result<Self::Value, A::Error> {
+        let (key, keys) = self.keys.split_first().expect("Keys are not empty");
+        let mut found = None;
+        let mut available = vec![];
+
+        while let Some(name) = map.next_key::<String>()? {
+            if name == *key {
+                found = Some(map.next_value_seed(KeyPathSeed { keys })?);
+            } else {
+                map.next_value::<IgnoredAny>()?;
+                available.push(name);
             }
-            _ => {
-                return Err(Error::ValueIsNotTable {
-                    path: path.to_string(),
+        }
+
+        Ok(found.unwrap_or_else(|| Err((key.to_string(), available))))
+    }
+}
+
+/// Parse a single config file, without resolving the files it extends.
+fn parse_config_file(path: &str, keys: &[&str]) -> Result<ConfigFile> {
+    let content = std::fs::read_to_string(path)?;
+    parse_config_str(path, &content, keys)
+}
+
+/// Parse the content of a single config file, as if it were read from
+/// `path`, without resolving the files it extends.
+fn parse_config_str(path: &str, content: &str, keys: &[&str]) -> Result<ConfigFile> {
+    let seed = KeyPathSeed { keys };
+    let extension = Path::new(path)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase());
+    let mut root = absolute(Path::new(path).parent().unwrap_or_else(|| Path::new("")));
+
+    // Fragments are part of the config file in the parent directory
+    if root.file_name().is_some_and(|name| name == FRAGMENTS_DIR) {
+        root.pop();
+    }
+
+    let variables = Variables {
+        config_dir: root.clone(),
+        directory: false,
+    };
+    let lookup = with_variables(variables, || match extension.as_deref() {
+        Some("json") => {
+            let mut deserializer = serde_json::Deserializer::from_str(content);
+            seed.deserialize(&mut deserializer)
+                .and_then(|lookup| deserializer.end().map(|_| lookup))
+                .map_err(|err| {
+                    let message = strip_location(err.to_string(), err.line(), err.column());
+                    let error = if err.line() == 0 {
+                        SourceError::new(path, content, None, &message)
+                    } else {
+                        SourceError::at(path, content, err.line(), err.column(), &message)
+                    };
+                    Error::InvalidConfig(error)
                 })
-            }
         }
+        Some("yaml" | "yml") => seed
+            .deserialize(serde_yaml::Deserializer::from_str(content))
+            .map_err(|err| {
+                let error = match err.location() {
+                    Some(location) => SourceError::new(
+                        path,
+                        content,
+                        Some(location.index()..location.index() + 1),
+                        &strip_location(err.to_string(), location.line(), location.column()),
+                    ),
+                    None => SourceError::new(path, content, None, &err.to_string()),
+                };
+                Error::InvalidConfig(error)
+            }),
+        _ => seed
+            .deserialize(toml::Deserializer::new(content))
+            .map_err(|err| {
+                Error::InvalidConfig(SourceError::new(path, content, err.span(), err.message()))
+            }),
+    })?;
+
+    let mut config_file = lookup.map_err(|(key, available)| Error::KeysNotFound {
+        suggestion: closest_match(&key, available.iter().map(String::as_str)).map(String::from),
+        keys: key,
+        path: path.to_string(),
+    })?;
+
+    for config in config_file.configs.values_mut() {
+        config.root = root.clone();
+    }
+    config_file.root = root;
+
+    Ok(config_file)
+}
+
+/// Remove the `" at line X column Y"` suffix that JSON and YAML errors
+/// append to their message, as the location is displayed separately.
+fn strip_location(message: String, line: usize, column: usize) -> String {
+    let suffix = format!(" at line {line} column {column}");
+
+    match message.strip_suffix(&suffix) {
+        Some(message) => message.to_string(),
+        None => message,
+    }
+}
+
+/// Parse a config file, and merge it on top of the config files it
+/// extends, recursively.
+///
+/// `chain` contains the config files being parsed, to detect cycles.
+fn parse_extended_config_file(
+    path: &str,
+    keys: &[&str],
+    chain: &mut Vec<(PathBuf, String)>,
+) -> Result<ConfigFile> {
+    let name = |path: &str, keys: &str| {
+        if keys.is_empty() {
+            path.to_string()
+        } else {
+            format!("{path}{SPLIT_PATH}{keys}")
+        }
+    };
+    let id = (
+        std::fs::canonicalize(path)?,
+        keys.join(&SPLIT_KEYS.to_string()),
+    );
+
+    if let Some(start) = chain.iter().position(|other| *other == id) {
+        let cycle = chain[start..]
+            .iter()
+            .chain(std::iter::once(&id))
+            .map(|(path, keys)| name(&path.to_string_lossy(), keys))
+            .join(" -> ");
+        return Err(Error::ExtendsCycle { cycle });
+    }
+
+    let config_file = parse_config_file(path, keys)?;
+    let directory = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+    let mut base = ConfigFile::default();
+
+    chain.push(id);
+
+    for value in config_file.extends.iter() {
+        let (base_path, base_keys) = split_path_and_keys(value);
+        let base_path = directory.join(base_path);
+        let extended = parse_extended_config_file(&base_path.to_string_lossy(), &base_keys, chain)
+            .map_err(|err| Error::Extends {
+                path: value.clone(),
+                by: name(path, &keys.join(&SPLIT_KEYS.to_string())),
+                source: Box::new(err),
+            })?;
+        base = extended.extend(base);
     }
 
-    println!("{}", toml_document);
-    let toml = toml_document.try_into()?;
-    Ok(toml)
+    for fragment in find_fragments(directory) {
+        let fragment = fragment.to_string_lossy();
+        let extended =
+            parse_extended_config_file(&fragment, &[], chain).map_err(|err| Error::Extends {
+                path: fragment.to_string(),
+                by: name(path, &keys.join(&SPLIT_KEYS.to_string())),
+                source: Box::new(err),
+            })?;
+        base = extended.extend(base);
+    }
+
+    chain.pop();
+    Ok(config_file.extend(base))
 }
 
+/// Return the fragments of the config files of a directory, i.e., the
+/// TOML, YAML and JSON files in its `.parts.d` subdirectory, sorted.
+fn find_fragments(directory: &Path) -> Vec<PathBuf> {
+    let entries = match std::fs::read_dir(directory.join(FRAGMENTS_DIR)) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let extension = path
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_lowercase());
+            path.is_file() && matches!(extension.as_deref(), Some("toml" | "yaml" | "yml" | "json"))
+        })
+        .sorted()
+        .collect()
+}
+
+/// Return the config files found in the subdirectories of a directory,
+/// at most one per directory, sorted.
+///
+/// Only standalone config files (e.g., `parts.toml`, not `Cargo.toml`) are
+/// looked for, and hidden and ignored directories are skipped, as by a
+/// [`Walker`] with the default settings.
+fn find_nested_config_files(directory: &Path) -> Vec<PathBuf> {
+    let names: Vec<&str> = POSSIBLE_CONFIG_PATHS
+        .iter()
+        .filter(|s| !s.contains(SPLIT_PATH))
+        .copied()
+        .collect();
+
+    walk_builder(&[directory.to_path_buf()], true, true)
+        .build()
+        .filter_map(|result| result.ok())
+        .filter(|de| de.depth() > 0 && de.file_type().is_some_and(|file_type| file_type.is_dir()))
+        .filter_map(|de| {
+            names
+                .iter()
+                .map(|name| de.path().join(name))
+                .find(|path| path.is_file())
+        })
+        .sorted()
+        .collect()
+}
+
+/// Try to parse a config file into a [`ConfigFile`] struct.
+///
+/// Files with a `.json`, `.yaml` or `.yml` extension are parsed as JSON or
+/// YAML, and all other files as TOML. If `keys` is not empty, the
+/// [`ConfigFile`] is parsed from the appropriate nested table (or object,
+/// or mapping). In all cases, errors in the document are reported with
+/// their location in the file, see [`SourceError`].
+///
+/// Config files listed in `extends` are parsed too, relative to the
+/// directory of the file extending them, see [`ConfigFile::extends`], as
+/// well as the fragments in the `.parts.d` directory next to the file.
+/// Fragments are extended implicitly, in alphabetical order, after the
+/// files listed in `extends`, and define parts relative to the directory
+/// of the file.
+///
+/// If the config file is [`ConfigFile::nested`], nested config files are
+/// merged into it.
+pub fn try_parse_config_file(path: &str, keys: Vec<&str>) -> Result<ConfigFile> {
+    parse_extended_config_file(path, &keys, &mut vec![])?.resolve()
+}
+
+/// Try to parse the content of a config file into a [`ConfigFile`] struct,
+/// as if it were read from `path`, e.g., to check it before writing it.
+///
+/// See [`try_parse_config_file`], except that the files it extends, the
+/// fragments and the nested config files are not parsed.
+pub fn try_parse_config_str(path: &str, content: &str, keys: Vec<&str>) -> Result<ConfigFile> {
+    let config_file = parse_config_str(path, content, &keys)?;
+    config_file.validate()?;
+    Ok(config_file)
+}
+
+/// Try to find a config file in the default locations, returning the first
+/// one that exists.
+///
+/// Candidates are, in order: `parts.toml`, `.parts.toml`, then the same
+/// names with the `.yaml`, `.yml` and `.json` extensions, and finally
+/// `Cargo.toml:metadata.parts`, `pyproject.toml:tool.parts` and
+/// `package.json:parts`.
+///
+/// They are looked for in the current directory, then in its parents, up
+/// to the root of the repository (i.e., the first directory containing
+/// `.git`) or of the filesystem. Directories and patterns of the parts are
+/// relative to the directory of the config file that is found.
 pub fn try_find_config_file() -> Result<ConfigFile> {
-    for s in POSSIBLE_CONFIG_PATHS.iter() {
-        let (path, keys) = split_path_and_keys(s);
-
-        match try_parse_config_file(path, keys) {
-            Ok(config_file) => {
-                return Ok(ConfigFile {
-                    config_file: s.to_string(),
-                    ..config_file
-                })
+    let mut rejected = vec![];
+    let mut directory = PathBuf::new();
+
+    loop {
+        for s in POSSIBLE_CONFIG_PATHS.iter() {
+            let (path, keys) = split_path_and_keys(s);
+            let path = directory.join(path);
+            let candidate = directory.join(s).to_string_lossy().into_owned();
+
+            if !path.is_file() {
+                info!("config file {candidate:?} was rejected: it does not exist");
+                rejected.push(format!("  {candidate}: does not exist"));
+                continue;
             }
-            Err(e) => match e {
-                Error::TomlDecode(_) => return Err(e),
-                _ => warn!("some error occured with parsing {:?}: {}\n", path, e),
-            },
+
+            match parse_extended_config_file(&path.to_string_lossy(), &keys, &mut vec![]) {
+                Ok(config_file) => {
+                    return ConfigFile {
+                        config_file: candidate,
+                        ..config_file
+                    }
+                    .resolve()
+                }
+                Err(e) => match e {
+                    Error::InvalidConfig(_)
+                    | Error::Extends { .. }
+                    | Error::ExtendsCycle { .. } => return Err(e),
+                    _ => {
+                        info!("config file {candidate:?} was rejected: {e}");
+                        rejected.push(format!("  {candidate}: {e}"));
+                    }
+                },
+            }
+        }
+
+        let current = absolute(&directory);
+
+        if current.join(".git").exists() || current.parent().is_none() {
+            break;
         }
+        directory.push("..");
     }
-    return Err(Error::NoConfigFileFound);
+
+    Err(Error::NoConfigFileFound {
+        candidates: rejected.join("\n"),
+    })
 }
 
-#[derive(Clone, Debug, Deserialize)]
+/// Load a config file from a `"<path>(:<keys>)"` value, or look for one
+/// in the default locations if `value` is `None`.
+///
+/// See [`try_parse_config_file`] and [`try_find_config_file`].
+pub fn load_config_file(value: Option<&str>) -> Result<ConfigFile> {
+    match value {
+        Some(value) => {
+            let (path, keys) = split_path_and_keys(value);
+            let config_file = try_parse_config_file(path, keys)?;
+            Ok(ConfigFile {
+                config_file: value.to_string(),
+                ..config_file
+            })
+        }
+        None => try_find_config_file(),
+    }
+}
+
+/// Collection of parts, as defined in a config file.
+///
+/// In the config file, the `default` key is the name of the default part,
+/// the `extends` key lists the config files it extends, the `nested` key
+/// enables nested config files, and every other key is a part.
+#[derive(Clone, Debug, Default)]
 pub struct ConfigFile {
-    #[serde(skip)]
+    /// Path (and optional keys) of the file this config was read from.
     pub config_file: String,
+    /// Absolute path of the directory of the config file, which the
+    /// directories and patterns of its parts are relative to.
+    pub root: PathBuf,
+    /// Name of the default part.
     pub default: Option<String>,
-    #[serde(flatten)]
-    pub configs: std::collections::HashMap<String, Config>,
+    /// Config files (`"<path>(:<keys>)"` values, relative to this file)
+    /// whose parts are inherited.
+    ///
+    /// Later files override earlier ones, and this file overrides them all.
+    /// Parts with the same name are merged according to [`Config::merge`].
+    /// Inherited directories and patterns are relative to the directory of
+    /// this file, as if the parts were defined in it.
+    pub extends: Vec<String>,
+    /// Whether config files in subdirectories are merged into this one.
+    ///
+    /// Parts of a nested config file are namespaced with the path of its
+    /// directory, e.g., part `api` of `team-a/parts.toml` is `team-a/api`,
+    /// and so are the parts it references. Their directories and patterns
+    /// are relative to the nested config file, as usual. This key is
+    /// ignored in nested config files themselves.
+    pub nested: bool,
+    /// Parts, indexed by name.
+    pub configs: HashMap<String, Config>,
+}
+
+impl<'de> Deserialize<'de> for ConfigFile {
+    // Parts are deserialized directly (rather than with `#[serde(flatten)]`),
+    // so that errors in them keep their location.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct ConfigFileVisitor;
+
+        impl<'de> Visitor<'de> for ConfigFileVisitor {
+            type Value = ConfigFile;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a table of parts")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> std::result::Result<Self::Value, A::Error> {
+                let mut default = None;
+                let mut extends = vec![];
+                let mut nested = false;
+                let mut configs = HashMap::new();
+
+                while let Some(key) = map.next_key::<String>()? {
+                    if key == "default" {
+                        default = Some(map.next_value()?);
+                    } else if key == "extends" {
+                        extends = map.next_value()?;
+                    } else if key == "nested" {
+                        nested = map.next_value()?;
+                    } else {
+                        let config = map.next_value()?;
+                        configs.insert(key, config);
+                    }
+                }
+
+                Ok(ConfigFile {
+                    config_file: String::new(),
+                    root: PathBuf::new(),
+                    default,
+                    extends,
+                    nested,
+                    configs,
+                })
+            }
+        }
+
+        deserializer.deserialize_map(ConfigFileVisitor)
+    }
 }
 
 impl ConfigFile {
+    /// Return the names of all parts, sorted.
+    pub fn part_names(&self) -> Vec<&str> {
+        self.configs.keys().map(String::as_str).sorted().collect()
+    }
+
+    /// Return the config of a given part.
+    pub fn get_part(&self, part: &str) -> Result<&Config> {
+        self.get(Some(part)).ok_or_else(|| Error::UnknownPart {
+            part: part.to_string(),
+            suggestion: self.get_closest_match(part).map(String::from),
+            available: match self.part_names().join(", ") {
+                names if names.is_empty() => "(none)".to_string(),
+                names => names,
+            },
+        })
+    }
+
+    /// Check that all references to other parts (in `include_parts` and
+    /// `exclude_parts`) exist, and that they do not form a cycle.
+    pub fn validate(&self) -> Result<()> {
+        fn visit<'a>(
+            config_file: &'a ConfigFile,
+            name: &'a str,
+            stack: &mut Vec<&'a str>,
+            done: &mut HashSet<&'a str>,
+        ) -> Result<()> {
+            if done.contains(name) {
+                return Ok(());
+            }
+            if let Some(start) = stack.iter().position(|other| *other == name) {
+                let mut cycle = stack[start..].to_vec();
+                cycle.push(name);
+                return Err(Error::PartCycle {
+                    cycle: cycle.join(" -> "),
+                });
+            }
+
+            let config = &config_file.configs[name];
+            stack.push(name);
+
+            for reference in config
+                .include_parts
+                .iter()
+                .chain(config.exclude_parts.iter())
+            {
+                if !config_file.configs.contains_key(reference) {
+                    return Err(Error::UnknownPartReference {
+                        part: name.to_string(),
+                        reference: reference.clone(),
+                        suggestion: config_file.get_closest_match(reference).map(String::from),
+                    });
+                }
+                visit(config_file, reference, stack, done)?;
+            }
+
+            stack.pop();
+            done.insert(name);
+            Ok(())
+        }
+
+        let mut done = HashSet::new();
+
+        for name in self.part_names() {
+            visit(self, name, &mut vec![], &mut done)?;
+        }
+        Ok(())
+    }
+
+    /// Return the [`Matcher`] of a given part, resolving references to
+    /// other parts.
+    pub fn matcher(&self, part: &str) -> Result<Matcher> {
+        let config = self.get_part(part)?;
+        let rules: Matcher = config.clone().into();
+
+        if config.include_parts.is_empty() && config.exclude_parts.is_empty() {
+            return Ok(rules);
+        }
+
+        let mut include = vec![rules];
+        for reference in config.include_parts.iter() {
+            include.push(self.matcher(reference)?);
+        }
+
+        let mut matchers = vec![Matcher::Any(include)];
+
+        // Exclude rules also apply to files from included parts
+        if !config.exclude_globs.is_empty() || !config.exclude_regexes.is_empty() {
+            matchers.push(Matcher::Not(Box::new(Matcher::Rules {
+                root: absolute(&config.root),
+                directories: vec![DirectoryMatcher::literal(&config.root)],
+                include: merge_globs_and_regexes(
+                    config.exclude_globs.clone(),
+                    config.exclude_regexes.clone(),
+                ),
+                exclude: RegexSet::empty(),
+            })));
+        }
+        for reference in config.exclude_parts.iter() {
+            matchers.push(Matcher::Not(Box::new(self.matcher(reference)?)));
+        }
+
+        Ok(Matcher::All(matchers))
+    }
+
+    /// Return a [`Walker`] over the files of a given part.
+    ///
+    /// Hidden and ignored files are skipped according to the settings of
+    /// the part itself, even for files from included parts.
+    pub fn walker(&self, part: &str) -> Result<Walker> {
+        let config = self.get_part(part)?;
+        Ok(Walker::new(
+            self.matcher(part)?,
+            config.ignore_hidden,
+            config.use_gitignore,
+        ))
+    }
+
+    /// Parse a set expression of parts (see [`Expr`]), e.g., as given on
+    /// the command line.
+    ///
+    /// The name of an existing part is never parsed, so that parts whose
+    /// names contain operators or whitespace can still be given alone.
+    pub fn expression(&self, expression: &str) -> Result<Expr> {
+        if self.configs.contains_key(expression) {
+            Ok(Expr::Part(expression.to_string()))
+        } else {
+            expression.parse()
+        }
+    }
+
+    /// Return a [`Walker`] over the files of a set expression of parts,
+    /// see [`ConfigFile::expression`].
+    ///
+    /// Hidden and ignored files are skipped according to the settings of
+    /// the leftmost part of the expression.
+    pub fn expression_walker(&self, expression: &str) -> Result<Walker> {
+        let expr = self.expression(expression)?;
+        let config = self.get_part(expr.parts()[0])?;
+        Ok(Walker::new(
+            expr.matcher(self)?,
+            config.ignore_hidden,
+            config.use_gitignore,
+        ))
+    }
+
+    /// Return the walkers of set expressions of parts, or of all parts if
+    /// `expressions` is empty, along with the expressions or part names.
+    pub fn walkers(&self, expressions: &[String]) -> Result<Vec<(String, Walker)>> {
+        if expressions.is_empty() {
+            self.part_names()
+                .into_iter()
+                .map(|name| Ok((name.to_string(), self.walker(name)?)))
+                .collect()
+        } else {
+            expressions
+                .iter()
+                .map(|expression| Ok((expression.clone(), self.expression_walker(expression)?)))
+                .collect()
+        }
+    }
+
     pub fn get(&self, key: Option<&str>) -> Option<&Config> {
         if let Some(key) = key {
             self.configs.get(key)
@@ -154,11 +871,90 @@ impl ConfigFile {
             false
         }
     }
-    
-    /*
-    pub fn get_closest_match(&self, key: &str) -> Option<String> {
-        ngrammatic::CorpusBuilder::new().fill(self.configs.keys()).finish().search(key, 0.5).first()
-    }*/
+
+    /// Merge this config file on top of a config file it extends.
+    ///
+    /// Inherited parts are rebased onto the directory of this config file,
+    /// see [`ConfigFile::extends`].
+    fn extend(self, base: ConfigFile) -> ConfigFile {
+        let mut configs = base.configs;
+
+        for (name, config) in self.configs {
+            let config = match configs.remove(&name) {
+                Some(base) => config.merge_into(base),
+                None => config,
+            };
+            configs.insert(name, config);
+        }
+        for config in configs.values_mut() {
+            config.root = self.root.clone();
+        }
+
+        ConfigFile {
+            config_file: self.config_file,
+            root: self.root,
+            default: self.default.or(base.default),
+            extends: self.extends,
+            nested: self.nested || base.nested,
+            configs,
+        }
+    }
+
+    /// Merge the nested config files if enabled, see [`ConfigFile::nested`],
+    /// and check the references between parts, see [`ConfigFile::validate`].
+    fn resolve(self) -> Result<ConfigFile> {
+        let config_file = if self.nested {
+            self.merge_nested()?
+        } else {
+            self
+        };
+
+        config_file.validate()?;
+        Ok(config_file)
+    }
+
+    /// Merge the config files found in the subdirectories of the directory
+    /// of this config file, see [`ConfigFile::nested`].
+    fn merge_nested(mut self) -> Result<ConfigFile> {
+        let current_dir = absolute(Path::new(""));
+
+        for path in find_nested_config_files(&self.root) {
+            let path = relative_to(&path, &current_dir);
+            let nested = parse_extended_config_file(&path.to_string_lossy(), &[], &mut vec![])?;
+            nested.validate()?;
+            let namespace = relative_to(&nested.root, &self.root)
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .join("/");
+
+            for (name, mut config) in nested.configs {
+                let name = format!("{namespace}/{name}");
+
+                for reference in config
+                    .include_parts
+                    .iter_mut()
+                    .chain(config.exclude_parts.iter_mut())
+                {
+                    *reference = format!("{namespace}/{reference}");
+                }
+                if self.configs.contains_key(&name) {
+                    return Err(Error::DuplicatePart {
+                        part: name,
+                        path: path.to_string_lossy().into_owned(),
+                    });
+                }
+                self.configs.insert(name, config);
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// Return the part name closest to a (probably mistyped) name, if any
+    /// is close enough.
+    pub fn get_closest_match(&self, key: &str) -> Option<&str> {
+        closest_match(key, self.part_names())
+    }
 
     pub fn write_list<T: WriteColor>(&self, stdout: &mut T) -> Result<()> {
         let mut filename_color = ColorSpec::new();
@@ -194,7 +990,7 @@ impl ConfigFile {
 
         for config_name in self.configs.keys().sorted() {
             stdout.write_all(b"- ")?;
-            if self.matches_default(&config_name) {
+            if self.matches_default(config_name) {
                 stdout.set_color(&key_color)?;
                 stdout.write_all(format!("{config_name} (default)\n").as_bytes())?;
                 stdout.reset()?;
@@ -208,31 +1004,200 @@ impl ConfigFile {
     }
 }
 
+/// Definition of a part, i.e., a set of files in one or more directories.
+///
+/// A file belongs to the part if it is inside one of its directories and
+/// matches any of the include rules (`regexes` and `globs`), or belongs to
+/// any of the `include_parts`, and it matches none of the exclude rules and
+/// belongs to none of the `exclude_parts`.
+///
+/// Directories and patterns may contain variables, e.g., `${OUT_DIR}`, see
+/// [`crate::interpolate`].
 #[derive(Clone, Debug, Deserialize)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
-    #[serde(default = "default_directory")]
-    pub directory: String,
+    /// Directory containing the files of the part. As for `directories`,
+    /// globs are allowed.
+    #[serde(default)]
+    #[serde(deserialize_with = "deserialize_directory")]
+    pub directory: Option<String>,
+    /// Directories containing the files of the part, in addition to
+    /// `directory`. Globs are allowed, e.g., `"crates/*"`.
+    #[serde(default)]
+    #[serde(deserialize_with = "deserialize_directories")]
+    pub directories: Vec<String>,
     #[serde(default = "default_true")]
     pub ignore_hidden: bool,
     #[serde(default = "default_true")]
     pub use_gitignore: bool,
     #[serde(default = "default_regexset")]
-    #[serde(with = "serde_regex")]
+    #[serde(deserialize_with = "deserialize_regexes")]
     pub regexes: RegexSet,
     #[serde(default)]
     #[serde(deserialize_with = "deserialize_globs")]
     pub globs: Vec<Glob>,
     #[serde(default = "default_regexset")]
-    #[serde(with = "serde_regex")]
+    #[serde(deserialize_with = "deserialize_regexes")]
     pub exclude_regexes: RegexSet,
     #[serde(default)]
     #[serde(deserialize_with = "deserialize_globs")]
     pub exclude_globs: Vec<Glob>,
+    /// Names of other parts whose files are included in this part.
+    #[serde(default)]
+    pub include_parts: Vec<String>,
+    /// Names of other parts whose files are excluded from this part.
+    #[serde(default)]
+    pub exclude_parts: Vec<String>,
+    /// How this part is merged with the part of the same name from an
+    /// extended config file.
+    #[serde(default)]
+    pub merge: Merge,
+    /// Directory of the config file defining the part, relative to which
+    /// its directories and patterns are resolved.
+    ///
+    /// It is empty, i.e., the current directory, if the part was not read
+    /// from a file.
+    #[serde(skip)]
+    pub root: PathBuf,
+}
+
+/// How a part overrides the part of the same name from an extended config
+/// file (see [`ConfigFile::extends`]).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Merge {
+    /// The part replaces the extended part entirely.
+    #[default]
+    Replace,
+    /// Patterns, `directories` and part references are appended to those of
+    /// the extended part, whose `directory`, `ignore_hidden` and
+    /// `use_gitignore` are kept.
+    Append,
+}
+
+impl Config {
+    /// Merge this part with the part of the same name from an extended
+    /// config file, according to [`Config::merge`].
+    fn merge_into(self, base: Config) -> Config {
+        let append_regexes = |base: RegexSet, regexes: RegexSet| {
+            RegexSet::new(base.patterns().iter().chain(regexes.patterns()))
+                .expect("This cannot fail")
+        };
+
+        match self.merge {
+            Merge::Replace => self,
+            Merge::Append => Config {
+                directories: [base.directories, self.directories].concat(),
+                regexes: append_regexes(base.regexes, self.regexes),
+                globs: [base.globs, self.globs].concat(),
+                exclude_regexes: append_regexes(base.exclude_regexes, self.exclude_regexes),
+                exclude_globs: [base.exclude_globs, self.exclude_globs].concat(),
+                include_parts: [base.include_parts, self.include_parts].concat(),
+                exclude_parts: [base.exclude_parts, self.exclude_parts].concat(),
+                ..base
+            },
+        }
+    }
 }
 
-fn default_directory() -> String {
-    "./".to_string()
+/// A pattern from the definition of a part.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pattern {
+    /// Key under which the pattern is defined, e.g., `"exclude_globs"`.
+    pub key: &'static str,
+    /// Index of the pattern in the list of that key.
+    pub index: usize,
+    /// Pattern, as written in the config file.
+    pub text: String,
+}
+
+/// Return the regexes and globs that match a path.
+fn matching_patterns(
+    path: &Path,
+    (regexes_key, regexes): (&'static str, &RegexSet),
+    (globs_key, globs): (&'static str, &[Glob]),
+) -> Vec<Pattern> {
+    let path = normalize(path);
+    let strl = path.to_string_lossy();
+    let mut patterns: Vec<Pattern> = regexes
+        .matches(strl.as_bytes())
+        .into_iter()
+        .map(|index| Pattern {
+            key: regexes_key,
+            index,
+            text: regexes.patterns()[index].clone(),
+        })
+        .collect();
+
+    for (index, glob) in globs.iter().enumerate() {
+        if glob.compile_matcher().is_match(strl.as_ref()) {
+            patterns.push(Pattern {
+                key: globs_key,
+                index,
+                text: glob.glob().to_string(),
+            });
+        }
+    }
+
+    patterns
+}
+
+impl Config {
+    /// Return a path, relative to the current directory, relative to
+    /// [`Config::root`] instead, as matched by the patterns of the part.
+    pub fn relative_path(&self, path: &Path) -> PathBuf {
+        relative_to(&absolute(path), &absolute(&self.root))
+    }
+
+    /// Return the include patterns (`regexes` and `globs`) that match a
+    /// path, relative to the current directory.
+    pub fn include_patterns_matching(&self, path: &Path) -> Vec<Pattern> {
+        matching_patterns(
+            &self.relative_path(path),
+            ("regexes", &self.regexes),
+            ("globs", &self.globs),
+        )
+    }
+
+    /// Return the exclude patterns (`exclude_regexes` and `exclude_globs`)
+    /// that match a path, relative to the current directory.
+    pub fn exclude_patterns_matching(&self, path: &Path) -> Vec<Pattern> {
+        matching_patterns(
+            &self.relative_path(path),
+            ("exclude_regexes", &self.exclude_regexes),
+            ("exclude_globs", &self.exclude_globs),
+        )
+    }
+
+    /// Return the directory patterns of the part, as written in the config
+    /// file: `directory` followed by `directories`, or the directory of the
+    /// config file if neither is set.
+    pub fn directory_patterns(&self) -> Vec<&str> {
+        let patterns: Vec<&str> = self
+            .directory
+            .iter()
+            .chain(self.directories.iter())
+            .map(String::as_str)
+            .collect();
+
+        if patterns.is_empty() {
+            vec!["./"]
+        } else {
+            patterns
+        }
+    }
+
+    /// Return the absolute paths of the directories of the part, with globs
+    /// expanded.
+    ///
+    /// Globs are expanded by walking the filesystem, see [`expand_directory`],
+    /// whereas [`Matcher`]s match them lexically.
+    pub fn directories(&self) -> Vec<PathBuf> {
+        self.directory_patterns()
+            .into_iter()
+            .flat_map(|pattern| expand_directory(&self.root, pattern))
+            .collect()
+    }
 }
 
 const fn default_true() -> bool {
@@ -255,3 +1220,293 @@ pub fn merge_globs_and_regexes(globs: Vec<Glob>, regexes: RegexSet) -> RegexSet
     .build()
     .expect("This cannot fail")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Create a project with the given files in a fresh temporary directory,
+    /// and return its absolute path.
+    fn project(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = std::env::temp_dir()
+            .join("parts-tests")
+            .join(format!("{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+
+        for (path, content) in files {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        root
+    }
+
+    /// Return the files of a part, relative to a directory, sorted.
+    fn walk(config_file: &ConfigFile, part: &str, directory: &Path) -> Vec<String> {
+        config_file
+            .walker(part)
+            .unwrap()
+            .collect()
+            .iter()
+            .map(|path| relative_to(&absolute(path), directory))
+            .map(|path| path.to_string_lossy().into_owned())
+            .sorted()
+            .collect()
+    }
+
+    /// Project with a shared config file, next to a local one extending it.
+    fn shared_project(name: &str, local: &str) -> PathBuf {
+        project(
+            name,
+            &[
+                (
+                    "shared/parts.toml",
+                    "[src]\nglobs = [\"src/**/*.rs\"]\n\n[docs]\nglobs = [\"*.md\"]\n",
+                ),
+                ("shared/src/shared.rs", ""),
+                ("local/parts.toml", local),
+                ("local/src/main.rs", ""),
+                ("local/lib/lib.rs", ""),
+                ("local/README.md", ""),
+            ],
+        )
+    }
+
+    #[test]
+    fn directory_globs() {
+        let root = project(
+            "directory_globs",
+            &[
+                (
+                    "parts.toml",
+                    "[crates]\ndirectories = [\"crates/*/src\"]\nglobs = [\"**/*.rs\"]\n\n\
+                     [legacy]\ndirectory = \"crates/*\"\nglobs = [\"**/*.rs\"]\n",
+                ),
+                ("crates/a/src/lib.rs", ""),
+                ("crates/a/build.rs", ""),
+                ("crates/main.rs", ""),
+            ],
+        );
+        let config_file =
+            try_parse_config_file(&root.join("parts.toml").to_string_lossy(), vec![]).unwrap();
+
+        assert_eq!(walk(&config_file, "crates", &root), ["crates/a/src/lib.rs"]);
+        assert_eq!(
+            walk(&config_file, "legacy", &root),
+            ["crates/a/build.rs", "crates/a/src/lib.rs"]
+        );
+
+        // Directory globs are matched without accessing the filesystem
+        let walker = config_file.walker("crates").unwrap();
+        assert!(walker.is_match(&root.join("crates/b/src/new.rs")));
+        assert!(!walker.is_match(&root.join("crates/b/new.rs")));
+        assert!(!walker.is_match(&root.join("crates/new.rs")));
+    }
+
+    #[test]
+    fn nested() {
+        let root = project(
+            "nested",
+            &[
+                (
+                    "parts.toml",
+                    "nested = true\n\n[all]\ninclude_parts = [\"team-a/api\"]\n\
+                     globs = [\"README.md\"]\n",
+                ),
+                ("README.md", ""),
+                (".gitignore", "ignored/\n"),
+                (".git/HEAD", ""),
+                (
+                    "team-a/parts.toml",
+                    "[api]\nglobs = [\"api/**\"]\n\n[all]\ninclude_parts = [\"api\"]\n",
+                ),
+                ("team-a/api/main.rs", ""),
+                ("team-a/README.md", ""),
+                ("ignored/parts.toml", "[ignored]\n"),
+                (".hidden/parts.toml", "[hidden]\n"),
+            ],
+        );
+        let config_file =
+            try_parse_config_file(&root.join("parts.toml").to_string_lossy(), vec![]).unwrap();
+
+        assert_eq!(
+            config_file.part_names(),
+            ["all", "team-a/all", "team-a/api"]
+        );
+        assert_eq!(
+            config_file.configs["team-a/all"].include_parts,
+            ["team-a/api"]
+        );
+        assert_eq!(
+            walk(&config_file, "all", &root),
+            ["README.md", "team-a/api/main.rs"]
+        );
+        assert_eq!(
+            walk(&config_file, "team-a/all", &root),
+            ["team-a/api/main.rs"]
+        );
+    }
+
+    #[test]
+    fn extends_replace() {
+        let root = shared_project(
+            "extends_replace",
+            "extends = [\"../shared/parts.toml\"]\n\n[src]\nglobs = [\"lib/*.rs\"]\n",
+        );
+        let local = root.join("local");
+        let config_file =
+            try_parse_config_file(&local.join("parts.toml").to_string_lossy(), vec![]).unwrap();
+
+        assert_eq!(walk(&config_file, "src", &local), ["lib/lib.rs"]);
+    }
+
+    #[test]
+    fn extends_append() {
+        let root = shared_project(
+            "extends_append",
+            "extends = [\"../shared/parts.toml\"]\n\n\
+             [src]\nmerge = \"append\"\nglobs = [\"lib/*.rs\"]\n",
+        );
+        let local = root.join("local");
+        let config_file =
+            try_parse_config_file(&local.join("parts.toml").to_string_lossy(), vec![]).unwrap();
+
+        assert_eq!(
+            walk(&config_file, "src", &local),
+            ["lib/lib.rs", "src/main.rs"]
+        );
+    }
+
+    #[test]
+    fn extends_inherit() {
+        let root = shared_project("extends_inherit", "extends = [\"../shared/parts.toml\"]\n");
+        let local = root.join("local");
+        let config_file =
+            try_parse_config_file(&local.join("parts.toml").to_string_lossy(), vec![]).unwrap();
+
+        assert_eq!(walk(&config_file, "src", &local), ["src/main.rs"]);
+        assert_eq!(walk(&config_file, "docs", &local), ["README.md"]);
+    }
+
+    #[test]
+    fn part_references() {
+        let root = project(
+            "part_references",
+            &[
+                (
+                    "parts.toml",
+                    "[src]\nglobs = [\"src/**/*.rs\"]\n\n\
+                     [tests]\nglobs = [\"tests/**\", \"src/**/*_test.rs\"]\n\n\
+                     [code]\ninclude_parts = [\"src\", \"tests\"]\n\n\
+                     [lib]\ninclude_parts = [\"src\"]\nexclude_parts = [\"tests\"]\n",
+                ),
+                ("src/lib.rs", ""),
+                ("src/lib_test.rs", ""),
+                ("tests/main.rs", ""),
+                ("README.md", ""),
+            ],
+        );
+        let config_file =
+            try_parse_config_file(&root.join("parts.toml").to_string_lossy(), vec![]).unwrap();
+
+        assert_eq!(
+            walk(&config_file, "code", &root),
+            ["src/lib.rs", "src/lib_test.rs", "tests/main.rs"]
+        );
+        assert_eq!(walk(&config_file, "lib", &root), ["src/lib.rs"]);
+    }
+
+    #[test]
+    fn part_reference_errors() {
+        let parse = |content| try_parse_config_str("parts.toml", content, vec![]);
+
+        match parse("[a]\ninclude_parts = [\"b\"]\n\n[b]\nexclude_parts = [\"a\"]\n") {
+            Err(Error::PartCycle { cycle }) => assert_eq!(cycle, "a -> b -> a"),
+            result => panic!("expected a cycle, got {result:?}"),
+        }
+        match parse("[a]\ninclude_parts = [\"a\"]\n") {
+            Err(Error::PartCycle { cycle }) => assert_eq!(cycle, "a -> a"),
+            result => panic!("expected a cycle, got {result:?}"),
+        }
+        match parse("[a]\nexclude_parts = [\"bc\"]\n\n[abc]\n") {
+            Err(Error::UnknownPartReference {
+                part,
+                reference,
+                suggestion,
+            }) => {
+                assert_eq!(part, "a");
+                assert_eq!(reference, "bc");
+                assert_eq!(suggestion.as_deref(), Some("abc"));
+            }
+            result => panic!("expected an unknown reference, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn embedded_keys() {
+        let (path, keys) = split_path_and_keys("package.json:parts");
+        let config_file = try_parse_config_str(
+            path,
+            r#"{"name": "app", "parts": {"default": "src", "src": {"globs": ["src/**"]}}}"#,
+            keys,
+        )
+        .unwrap();
+        assert_eq!(config_file.part_names(), ["src"]);
+        assert!(config_file.matches_default("src"));
+
+        let config_file = try_parse_config_str(
+            "config.yaml",
+            "tool:\n  parts:\n    docs:\n      globs: [\"*.md\"]\n",
+            vec!["tool", "parts"],
+        )
+        .unwrap();
+        assert_eq!(config_file.part_names(), ["docs"]);
+
+        match try_parse_config_str(
+            "config.json",
+            r#"{"tool": {"parts": {}}}"#,
+            vec!["tool", "part"],
+        ) {
+            Err(Error::KeysNotFound {
+                keys, suggestion, ..
+            }) => {
+                assert_eq!(keys, "part");
+                assert_eq!(suggestion.as_deref(), Some("parts"));
+            }
+            result => panic!("expected missing keys, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn part_names() {
+        let root = project(
+            "part_names",
+            &[
+                (
+                    "parts.toml",
+                    "[\"my part\"]\nglobs = [\"*.md\"]\n\n[\"a&b\"]\nglobs = [\"*.rs\"]\n\n\
+                     [all]\nglobs = [\"*\"]\n",
+                ),
+                ("README.md", ""),
+                ("main.rs", ""),
+            ],
+        );
+        let config_file =
+            try_parse_config_file(&root.join("parts.toml").to_string_lossy(), vec![]).unwrap();
+
+        // Names that are not valid in expressions can be given alone
+        for (name, file) in [("my part", "README.md"), ("a&b", "main.rs")] {
+            assert_eq!(
+                config_file.expression(name).unwrap(),
+                Expr::Part(name.to_string())
+            );
+            let walker = config_file.expression_walker(name).unwrap();
+            assert!(walker.is_match(&root.join(file)));
+        }
+        assert!(config_file.expression("all - my part").is_err());
+        assert_eq!(
+            config_file.expression("all-a").unwrap(),
+            Expr::Part("all-a".to_string())
+        );
+    }
+}
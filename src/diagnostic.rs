@@ -0,0 +1,147 @@
+//! Rendering of errors located in config files.
+use std::fmt;
+use std::ops::Range;
+
+/// Line of a config file an error points to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Location {
+    /// Line number, starting at 1.
+    line: usize,
+    /// Column number, in characters, starting at 1.
+    column: usize,
+    /// Contents of the line.
+    text: String,
+    /// Number of characters of the line covered by the error.
+    width: usize,
+}
+
+/// Error at a given location of a config file.
+///
+/// It is displayed with the path, line and column of the error, followed
+/// by the offending line with carets under the erroneous value, e.g.:
+///
+/// ```text
+/// error parsing glob 'a[': unclosed character class; missing ']'
+///  --> parts.toml:4:9
+///   |
+/// 4 | globs = ["a[", "b"]
+///   |         ^^^^^^^^^^^
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceError {
+    /// Path of the config file.
+    pub path: String,
+    pub message: String,
+    location: Option<Location>,
+}
+
+impl SourceError {
+    /// Create an error from the contents of a config file, and the span
+    /// (in bytes) of the error in the contents, if known.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use parts::diagnostic::SourceError;
+    /// let content = "[src]\nglobs = 1\n";
+    /// let error = SourceError::new("parts.toml", content, Some(14..15), "expected an array");
+    /// assert_eq!(error.line(), Some(2));
+    /// assert_eq!(error.column(), Some(9));
+    /// ```
+    pub fn new(path: &str, content: &str, span: Option<Range<usize>>, message: &str) -> Self {
+        let location = span.map(|span| {
+            let start = floor_char_boundary(content, span.start);
+            let end = floor_char_boundary(content, span.end.max(start));
+            let line_start = content[..start].rfind('\n').map_or(0, |i| i + 1);
+            let line_end = content[start..]
+                .find('\n')
+                .map_or(content.len(), |i| start + i);
+
+            Location {
+                line: content[..start].matches('\n').count() + 1,
+                column: content[line_start..start].chars().count() + 1,
+                text: content[line_start..line_end]
+                    .trim_end_matches('\r')
+                    .to_string(),
+                width: content[start..end.min(line_end)].chars().count().max(1),
+            }
+        });
+
+        SourceError {
+            path: path.to_string(),
+            message: message.trim_end().to_string(),
+            location,
+        }
+    }
+
+    /// Create an error from the contents of a config file, and the line and
+    /// column (in bytes) of the error, both starting at 1.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use parts::diagnostic::SourceError;
+    /// let content = "src:\n  globs: 1\n";
+    /// let error = SourceError::at("parts.yaml", content, 2, 10, "expected a sequence");
+    /// assert_eq!(error.line(), Some(2));
+    /// assert_eq!(error.column(), Some(10));
+    /// ```
+    pub fn at(path: &str, content: &str, line: usize, column: usize, message: &str) -> Self {
+        let line_start: usize = content
+            .split_inclusive('\n')
+            .take(line.saturating_sub(1))
+            .map(str::len)
+            .sum();
+        let start = line_start + column.saturating_sub(1);
+        SourceError::new(path, content, Some(start..start + 1), message)
+    }
+
+    /// Line number of the error, starting at 1, if known.
+    pub fn line(&self) -> Option<usize> {
+        self.location.as_ref().map(|location| location.line)
+    }
+
+    /// Column number of the error, in characters, starting at 1, if known.
+    pub fn column(&self) -> Option<usize> {
+        self.location.as_ref().map(|location| location.column)
+    }
+}
+
+/// Return the largest index not greater than `index` that is on a char
+/// boundary of `s`.
+fn floor_char_boundary(s: &str, index: usize) -> usize {
+    let mut index = index.min(s.len());
+
+    while !s.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.message)?;
+
+        let location = match &self.location {
+            Some(location) => location,
+            None => return write!(f, " --> {}", self.path),
+        };
+        let gutter = " ".repeat(location.line.to_string().len());
+        // Keep tabs, so that carets are aligned with the text above
+        let padding: String = location
+            .text
+            .chars()
+            .take(location.column - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        writeln!(
+            f,
+            "{gutter}--> {}:{}:{}",
+            self.path, location.line, location.column
+        )?;
+        writeln!(f, "{gutter} |")?;
+        writeln!(f, "{} | {}", location.line, location.text)?;
+        write!(f, "{gutter} | {padding}{}", "^".repeat(location.width))
+    }
+}
@@ -1,4 +1,5 @@
 //! Error and Result structure used all across this crate.
+use crate::diagnostic::SourceError;
 
 /// Enumeration of all possible error types.
 #[derive(Debug, thiserror::Error)]
@@ -12,20 +13,116 @@ pub enum Error {
     /// Error from deserializing TOML (see [toml::de::Error]).
     #[error(transparent)]
     TomlDecode(#[from] toml::de::Error),
-    /// Error accessing a key in a TOML document.
-    #[error("TOML file {path:?} does not contain keys {keys:?}")]
-    KeysNotFound { keys: String, path: String },
-    /// Error parsing a TOML value in a table.
-    #[error("TOML file {path:?} does not contain (nested) tables as expected")]
-    ValueIsNotTable { path: String },
-    /// Error parsing a TOML value in a table.
-    #[error("no TOML config file was found, use verbose output (`-v`) for more details")]
-    NoConfigFileFound,
+    /// Error from serializing TOML (see [toml::ser::Error]).
+    #[error(transparent)]
+    TomlEncode(#[from] toml::ser::Error),
+    /// Error from serializing JSON (see [serde_json::Error]).
+    #[error(transparent)]
+    JsonEncode(#[from] serde_json::Error),
+    /// Error located in a config file, e.g., invalid syntax, an invalid
+    /// pattern or an unknown key (see [SourceError]).
+    #[error("{0}")]
+    InvalidConfig(SourceError),
+    /// Error in a config file extended by another config file.
+    #[error("invalid config file {path:?}, extended by {by:?}")]
+    Extends {
+        path: String,
+        by: String,
+        source: Box<Error>,
+    },
+    /// Config files extend each other in a cycle.
+    #[error("config files extend each other in a cycle: {cycle}")]
+    ExtendsCycle { cycle: String },
+    /// Error accessing a key in a config file.
+    #[error("config file {path:?} does not contain keys {keys:?}{}", did_you_mean(.suggestion))]
+    KeysNotFound {
+        keys: String,
+        path: String,
+        suggestion: Option<String>,
+    },
+    /// A config file cannot be created, as it already exists.
+    #[error("config {value:?} already exists, use `--dry-run` to preview a new one")]
+    ConfigAlreadyExists { value: String },
+    /// A config file cannot be created at the given location.
+    #[error("cannot create a config at {value:?}, expected one of: {available}")]
+    UnsupportedInitOutput { value: String, available: String },
+    /// No config file was found in the current directory or its parents.
+    #[error("no config file was found, candidates considered:\n{candidates}")]
+    NoConfigFileFound {
+        /// Candidates, one per line, with the reason they were rejected.
+        candidates: String,
+    },
     /// Specified config file value is invalid.
-    #[error("user-defined TOML config file value {value:?} does not exist")]
+    #[error("user-defined config file value {value:?} does not exist")]
     ConfigFileDoesNotExist { value: String },
-    #[error("unknown part name: {part:?}")]
-    UnknownPart { part: String },
+    /// Variables of a config value could not be interpolated.
+    #[error("cannot interpolate {value:?}: {reason}")]
+    Interpolation { value: String, reason: String },
+    /// Part name is not defined in the config file.
+    #[error("unknown part name: {part:?}{}, available parts are: {available}", did_you_mean(.suggestion))]
+    UnknownPart {
+        part: String,
+        suggestion: Option<String>,
+        available: String,
+    },
+    /// Set expression of parts could not be parsed.
+    #[error("invalid part expression {expression:?}: {reason}")]
+    InvalidExpression { expression: String, reason: String },
+    /// Part name cannot be used in set expressions of parts.
+    #[error("part name {part:?} cannot be used in set expressions: {reason}")]
+    InvalidPartName { part: String, reason: String },
+    /// A part references another part that does not exist.
+    #[error("part {part:?} references unknown part {reference:?}{}", did_you_mean(.suggestion))]
+    UnknownPartReference {
+        part: String,
+        reference: String,
+        suggestion: Option<String>,
+    },
+    /// A part of a nested config file has the same namespaced name as an
+    /// existing part.
+    #[error("part {part:?} from nested config file {path:?} is already defined")]
+    DuplicatePart { part: String, path: String },
+    /// Parts reference each other in a cycle.
+    #[error("parts reference each other in a cycle: {cycle}")]
+    PartCycle { cycle: String },
+    /// Error from watching the filesystem (see [notify::Error]).
+    #[cfg(feature = "monitor")]
+    #[error(transparent)]
+    Notify(#[from] notify::Error),
+    /// Specified output format is invalid.
+    #[error("invalid output format {format:?}: {reason}")]
+    InvalidFormat { format: String, reason: String },
+    /// Output template refers to a field that does not exist.
+    #[error("unknown template field {{{field}}}, available fields are: {available}")]
+    UnknownTemplateField { field: String, available: String },
+    /// Output format is not supported by a command.
+    #[error("`{command}` does not support this output format, supported formats are: {supported}")]
+    UnsupportedFormat { command: String, supported: String },
+    /// Too many files do not belong to any part.
+    #[error("{uncovered} file(s) do not belong to any part, coverage is {coverage:.1}% (required: {required:.1}%)")]
+    InsufficientCoverage {
+        uncovered: usize,
+        coverage: f64,
+        required: f64,
+    },
+    /// Problems were found when checking a config file.
+    #[error("config check found {errors} error(s) and {warnings} warning(s)")]
+    CheckFailed { errors: usize, warnings: usize },
+    /// No snapshot was saved for a given part.
+    #[error("no snapshot of part {part:?} found at {path:?}, save one with `parts snapshot save`")]
+    SnapshotNotFound { part: String, path: String },
+    /// Error returned by a git command.
+    #[cfg(feature = "git")]
+    #[error("`{command}` failed: {stderr}")]
+    Git { command: String, stderr: String },
+}
+
+/// Format an optional suggestion as a hint to append to an error message.
+fn did_you_mean(suggestion: &Option<String>) -> String {
+    match suggestion {
+        Some(suggestion) => format!(" (did you mean {suggestion:?}?)"),
+        None => String::new(),
+    }
 }
 
 /// Result type alias with error type defined above (see [Error]).
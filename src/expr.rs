@@ -0,0 +1,387 @@
+//! Set-algebra expressions on parts.
+//!
+//! Expressions combine part names with the following operators, from
+//! highest to lowest precedence:
+//!
+//! - `!a`: files that are not in `a` (in the current directory);
+//! - `a & b`: files that are in both `a` and `b`;
+//! - `a | b`: files that are in `a` or `b`, and `a - b`: files that are
+//!   in `a` but not in `b`.
+//!
+//! Parentheses can be used for grouping. As part names may contain
+//! dashes, `-` is only parsed as an operator at the start of a token,
+//! i.e., `a - b` and `(a|b)-c` are differences, but `a-b` is a part name.
+//!
+//! Part names that contain whitespace or the other operators, or that start
+//! with a dash, can therefore not be used in expressions, see
+//! [`validate_part_name`]. Such parts can still be given alone, see
+//! [`ConfigFile::expression`].
+//!
+//! # Examples
+//!
+//! ```
+//! # use parts::expr::Expr;
+//! let expr: Expr = "(docs | examples) - generated".parse().unwrap();
+//! assert_eq!(expr.parts(), vec!["docs", "examples", "generated"]);
+//!
+//! let expr: Expr = "src".parse().unwrap();
+//! assert_eq!(expr, Expr::Part("src".to_string()));
+//! ```
+use crate::config::ConfigFile;
+use crate::error::{Error, Result};
+use crate::walk::Matcher;
+use std::str::FromStr;
+
+/// Set-algebra expression on parts.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expr {
+    Part(String),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Diff(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Name(String),
+    Not,
+    And,
+    Or,
+    Minus,
+    Open,
+    Close,
+}
+
+const OPERATORS: &[char] = &['!', '&', '|', '(', ')'];
+
+/// Check that a part name can be used in expressions, i.e., that it is
+/// not empty, does not start with `-`, and does not contain whitespace,
+/// `!`, `&`, `|`, `(` or `)`.
+pub fn validate_part_name(name: &str) -> Result<()> {
+    let reason = if name.is_empty() {
+        "it is empty"
+    } else if name.starts_with('-') {
+        "it starts with '-'"
+    } else if name.contains(char::is_whitespace) {
+        "it contains whitespace"
+    } else if name.contains(OPERATORS) {
+        "it contains one of '!', '&', '|', '(' or ')'"
+    } else {
+        return Ok(());
+    };
+
+    Err(Error::InvalidPartName {
+        part: name.to_string(),
+        reason: reason.to_string(),
+    })
+}
+
+fn tokenize(s: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '!' => Token::Not,
+            '&' => Token::And,
+            '|' => Token::Or,
+            '-' => Token::Minus,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            c => {
+                let mut name = c.to_string();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || OPERATORS.contains(&c) {
+                        break;
+                    }
+                    name.push(c);
+                    chars.next();
+                }
+                Token::Name(name)
+            }
+        };
+        tokens.push(token);
+    }
+
+    tokens
+}
+
+/// Recursive descent parser.
+struct Parser<'a> {
+    expression: &'a str,
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, reason: &str) -> Error {
+        Error::InvalidExpression {
+            expression: self.expression.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    /// `union := intersection (('|' | '-') intersection)*`
+    fn union(&mut self) -> Result<Expr> {
+        let mut expr = self.intersection()?;
+
+        loop {
+            match self.peek() {
+                Some(Token::Or) => {
+                    self.next();
+                    expr = Expr::Or(Box::new(expr), Box::new(self.intersection()?));
+                }
+                Some(Token::Minus) => {
+                    self.next();
+                    expr = Expr::Diff(Box::new(expr), Box::new(self.intersection()?));
+                }
+                _ => return Ok(expr),
+            }
+        }
+    }
+
+    /// `intersection := complement ('&' complement)*`
+    fn intersection(&mut self) -> Result<Expr> {
+        let mut expr = self.complement()?;
+
+        while self.peek() == Some(&Token::And) {
+            self.next();
+            expr = Expr::And(Box::new(expr), Box::new(self.complement()?));
+        }
+
+        Ok(expr)
+    }
+
+    /// `complement := '!' complement | '(' union ')' | name`
+    fn complement(&mut self) -> Result<Expr> {
+        match self.next() {
+            Some(Token::Not) => Ok(Expr::Not(Box::new(self.complement()?))),
+            Some(Token::Open) => {
+                let expr = self.union()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => Err(self.error("expected ')'")),
+                }
+            }
+            Some(Token::Name(name)) => Ok(Expr::Part(name)),
+            Some(_) => Err(self.error("expected a part name, '!' or '('")),
+            None => Err(self.error("unexpected end of expression")),
+        }
+    }
+}
+
+impl FromStr for Expr {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parser = Parser {
+            expression: s,
+            tokens: tokenize(s),
+            position: 0,
+        };
+        let expr = parser.union()?;
+
+        if parser.peek().is_some() {
+            return Err(parser.error("unexpected token after end of expression"));
+        }
+
+        Ok(expr)
+    }
+}
+
+/// Write an operand, in parentheses if its operator binds less tightly than
+/// `precedence`.
+fn write_operand(f: &mut std::fmt::Formatter<'_>, expr: &Expr, precedence: u8) -> std::fmt::Result {
+    if expr.precedence() < precedence {
+        write!(f, "({expr})")
+    } else {
+        write!(f, "{expr}")
+    }
+}
+
+impl std::fmt::Display for Expr {
+    /// Write the expression in canonical form, i.e., with spaces around
+    /// binary operators and only the necessary parentheses, so that, e.g.,
+    /// `src|tests` and `(src) | tests` are written the same.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (a, operator, b) = match self {
+            Expr::Part(name) => return write!(f, "{name}"),
+            Expr::Not(expr) => {
+                write!(f, "!")?;
+                return write_operand(f, expr, self.precedence());
+            }
+            Expr::And(a, b) => (a, "&", b),
+            Expr::Or(a, b) => (a, "|", b),
+            Expr::Diff(a, b) => (a, "-", b),
+        };
+
+        // Operators are left-associative
+        write_operand(f, a, self.precedence())?;
+        write!(f, " {operator} ")?;
+        write_operand(f, b, self.precedence() + 1)
+    }
+}
+
+impl Expr {
+    /// Return how tightly the outermost operator binds, see the module
+    /// documentation.
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Part(_) | Expr::Not(_) => 3,
+            Expr::And(..) => 2,
+            Expr::Or(..) | Expr::Diff(..) => 1,
+        }
+    }
+
+    /// Return the names of the parts in the expression, from left to right.
+    pub fn parts(&self) -> Vec<&str> {
+        match self {
+            Expr::Part(name) => vec![name.as_str()],
+            Expr::Not(expr) => expr.parts(),
+            Expr::And(a, b) | Expr::Or(a, b) | Expr::Diff(a, b) => {
+                let mut parts = a.parts();
+                parts.extend(b.parts());
+                parts
+            }
+        }
+    }
+
+    /// Return the [`Matcher`] of the expression.
+    pub fn matcher(&self, config_file: &ConfigFile) -> Result<Matcher> {
+        Ok(match self {
+            Expr::Part(name) => config_file.matcher(name)?,
+            Expr::Not(expr) => Matcher::Not(Box::new(expr.matcher(config_file)?)),
+            Expr::And(a, b) => Matcher::All(vec![a.matcher(config_file)?, b.matcher(config_file)?]),
+            Expr::Or(a, b) => Matcher::Any(vec![a.matcher(config_file)?, b.matcher(config_file)?]),
+            Expr::Diff(a, b) => Matcher::All(vec![
+                a.matcher(config_file)?,
+                Matcher::Not(Box::new(b.matcher(config_file)?)),
+            ]),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Expr {
+        s.parse().unwrap()
+    }
+
+    fn part(name: &str) -> Box<Expr> {
+        Box::new(Expr::Part(name.to_string()))
+    }
+
+    #[test]
+    fn precedence() {
+        // `!` binds tighter than `&`, which binds tighter than `|` and `-`
+        assert_eq!(
+            parse("a | !b & c"),
+            Expr::Or(
+                part("a"),
+                Box::new(Expr::And(Box::new(Expr::Not(part("b"))), part("c")))
+            )
+        );
+        assert_eq!(
+            parse("a - b & c"),
+            Expr::Diff(part("a"), Box::new(Expr::And(part("b"), part("c"))))
+        );
+        // `|` and `-` have the same precedence, and are left-associative
+        assert_eq!(
+            parse("a - b | c"),
+            Expr::Or(Box::new(Expr::Diff(part("a"), part("b"))), part("c"))
+        );
+        assert_eq!(
+            parse("a | b - c"),
+            Expr::Diff(Box::new(Expr::Or(part("a"), part("b"))), part("c"))
+        );
+    }
+
+    #[test]
+    fn minus() {
+        assert_eq!(parse("a-b"), Expr::Part("a-b".to_string()));
+        assert_eq!(parse("a -b"), Expr::Diff(part("a"), part("b")));
+        assert_eq!(parse("a - b"), Expr::Diff(part("a"), part("b")));
+        assert_eq!(
+            parse("(a|b)-c"),
+            Expr::Diff(Box::new(Expr::Or(part("a"), part("b"))), part("c"))
+        );
+        assert_eq!(parse("team-a/api"), Expr::Part("team-a/api".to_string()));
+    }
+
+    #[test]
+    fn parentheses() {
+        assert_eq!(parse("(a)"), Expr::Part("a".to_string()));
+        assert_eq!(
+            parse("a & (b | c)"),
+            Expr::And(part("a"), Box::new(Expr::Or(part("b"), part("c"))))
+        );
+        assert_eq!(
+            parse("!(a - b)"),
+            Expr::Not(Box::new(Expr::Diff(part("a"), part("b"))))
+        );
+        assert_eq!(parse("((a))&b"), Expr::And(part("a"), part("b")));
+    }
+
+    #[test]
+    fn display() {
+        for (expression, canonical) in [
+            ("src", "src"),
+            ("src|tests", "src | tests"),
+            ("((a))&b", "a & b"),
+            ("a | !b & c", "a | !b & c"),
+            ("(a | b) & c", "(a | b) & c"),
+            ("a - (b - c)", "a - (b - c)"),
+            ("(a - b) - c", "a - b - c"),
+            ("!(a-b)", "!a-b"),
+            ("!(a - b)", "!(a - b)"),
+            ("!!a", "!!a"),
+        ] {
+            let expr = parse(expression);
+            assert_eq!(expr.to_string(), canonical);
+            assert_eq!(parse(canonical), expr);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        for expression in [
+            "", "(a", "a)", "a &", "& a", "a b", "()", "a | | b", "-a", "!",
+        ] {
+            assert!(
+                matches!(
+                    expression.parse::<Expr>(),
+                    Err(Error::InvalidExpression { .. })
+                ),
+                "{expression:?} should not parse"
+            );
+        }
+    }
+
+    #[test]
+    fn part_names() {
+        for name in ["src", "a-b", "team-a/api", "a.b_c", "crates/*"] {
+            assert!(validate_part_name(name).is_ok(), "{name:?} should be valid");
+            assert_eq!(parse(name), Expr::Part(name.to_string()));
+        }
+        for name in ["", "a b", "-a", "a!", "a&b", "a|b", "(a)", "a\tb"] {
+            assert!(
+                matches!(validate_part_name(name), Err(Error::InvalidPartName { .. })),
+                "{name:?} should be invalid"
+            );
+        }
+    }
+}
@@ -0,0 +1,265 @@
+//! Git integration, used to map changes in a repository onto parts.
+//!
+//! All the functions in this module shell out to the `git` executable,
+//! which must be available in the `PATH`.
+use crate::error::{Error, Result};
+use crate::walk::{absolute, relative_to};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Run a git command in the current directory and return its standard output.
+fn git(args: &[&str]) -> Result<Vec<u8>> {
+    let output = Command::new("git").args(args).output()?;
+
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(Error::Git {
+            command: format!("git {}", args.join(" ")),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        })
+    }
+}
+
+#[cfg(unix)]
+fn bytes_to_path(bytes: &[u8]) -> PathBuf {
+    use std::os::unix::ffi::OsStrExt;
+    std::ffi::OsStr::from_bytes(bytes).into()
+}
+
+#[cfg(not(unix))]
+fn bytes_to_path(bytes: &[u8]) -> PathBuf {
+    String::from_utf8_lossy(bytes).into_owned().into()
+}
+
+/// Split the output of a git command run with `-z` into paths.
+fn split_paths(output: &[u8]) -> impl Iterator<Item = PathBuf> + '_ {
+    output
+        .split(|&byte| byte == b'\0')
+        .filter(|bytes| !bytes.is_empty())
+        .map(bytes_to_path)
+}
+
+/// Return a function that rebases paths relative to the root of the
+/// repository onto the current directory, e.g., into `../docs/a.md`.
+fn rebase() -> Result<impl Fn(&Path) -> PathBuf> {
+    let output = git(&["rev-parse", "--show-toplevel"])?;
+    let toplevel = bytes_to_path(output.strip_suffix(b"\n").unwrap_or(&output));
+    let current_dir = absolute(Path::new(""));
+
+    Ok(move |path: &Path| relative_to(&toplevel.join(path), &current_dir))
+}
+
+/// Return the best common ancestor between `rev` and `HEAD`.
+pub fn merge_base(rev: &str) -> Result<String> {
+    let output = git(&["merge-base", rev, "HEAD"])?;
+    Ok(String::from_utf8_lossy(&output).trim().to_string())
+}
+
+/// Return the files that changed since `rev`.
+///
+/// If `head` is true, only committed changes (up to `HEAD`) are considered.
+/// Otherwise, the working tree is compared against `rev`, and untracked
+/// files are included as well.
+///
+/// Renamed files are reported under both their old and new names.
+/// Paths are relative to the current directory, including those of files
+/// located outside of it, e.g., `../docs/a.md`.
+pub fn changed_files(rev: &str, head: bool) -> Result<Vec<PathBuf>> {
+    let mut args = vec!["diff", "--name-only", "-z", "--no-renames", rev];
+
+    if head {
+        args.push("HEAD");
+    }
+
+    args.push("--");
+
+    let rebase = rebase()?;
+    let mut files: Vec<PathBuf> = split_paths(&git(&args)?).collect();
+
+    if !head {
+        // `:/` lists the files of the whole repository, not only of the
+        // current directory
+        let untracked = git(&[
+            "ls-files",
+            "--others",
+            "--exclude-standard",
+            "--full-name",
+            "-z",
+            ":/",
+        ])?;
+        files.extend(split_paths(&untracked));
+    }
+
+    let mut files: Vec<PathBuf> = files.iter().map(|file| rebase(file)).collect();
+
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// Status of a single file in the working tree, as reported by `git status`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FileStatus {
+    /// The file has changes in the index.
+    pub staged: bool,
+    /// The file has unstaged changes in the working tree.
+    pub modified: bool,
+    /// The file is not tracked by git.
+    pub untracked: bool,
+    /// The file was deleted, either in the index or in the working tree.
+    pub deleted: bool,
+}
+
+impl FileStatus {
+    /// Parse the two-letter `XY` status code of `git status --porcelain`.
+    fn from_code(x: u8, y: u8) -> Self {
+        if x == b'?' && y == b'?' {
+            return FileStatus {
+                untracked: true,
+                ..Default::default()
+            };
+        }
+        FileStatus {
+            staged: matches!(x, b'M' | b'T' | b'A' | b'D' | b'R' | b'C'),
+            modified: matches!(y, b'M' | b'T') || x == b'U' || y == b'U',
+            untracked: false,
+            deleted: x == b'D' || y == b'D',
+        }
+    }
+}
+
+/// Return the status of every changed or untracked file in the working tree.
+///
+/// Paths are relative to the current directory, including those of files
+/// located outside of it, e.g., `../docs/a.md`.
+pub fn status() -> Result<Vec<(PathBuf, FileStatus)>> {
+    let rebase = rebase()?;
+    let output = git(&["status", "--porcelain=v1", "-z", "--untracked-files=all"])?;
+
+    Ok(parse_status(&output)
+        .into_iter()
+        .map(|(path, status)| (rebase(&path), status))
+        .collect())
+}
+
+/// Parse the output of `git status --porcelain=v1 -z`, whose paths are
+/// relative to the root of the repository.
+fn parse_status(output: &[u8]) -> Vec<(PathBuf, FileStatus)> {
+    let mut files = Vec::new();
+    let mut entries = output.split(|&byte| byte == b'\0');
+
+    while let Some(entry) = entries.next() {
+        if entry.len() < 4 {
+            continue;
+        }
+        let (x, y) = (entry[0], entry[1]);
+
+        // Renames and copies are followed by the original path, which we skip
+        if matches!(x, b'R' | b'C') {
+            entries.next();
+        }
+
+        files.push((bytes_to_path(&entry[3..]), FileStatus::from_code(x, y)));
+    }
+
+    files
+}
+
+/// Number of files in each status category.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StatusCounts {
+    pub modified: usize,
+    pub staged: usize,
+    pub untracked: usize,
+    pub deleted: usize,
+}
+
+impl StatusCounts {
+    pub fn add(&mut self, status: &FileStatus) {
+        self.modified += status.modified as usize;
+        self.staged += status.staged as usize;
+        self.untracked += status.untracked as usize;
+        self.deleted += status.deleted as usize;
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.modified == 0 && self.staged == 0 && self.untracked == 0 && self.deleted == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(staged: bool, modified: bool, untracked: bool, deleted: bool) -> FileStatus {
+        FileStatus {
+            staged,
+            modified,
+            untracked,
+            deleted,
+        }
+    }
+
+    #[test]
+    fn status_codes() {
+        assert_eq!(
+            FileStatus::from_code(b'M', b' '),
+            status(true, false, false, false)
+        );
+        assert_eq!(
+            FileStatus::from_code(b' ', b'M'),
+            status(false, true, false, false)
+        );
+        assert_eq!(
+            FileStatus::from_code(b'M', b'M'),
+            status(true, true, false, false)
+        );
+        assert_eq!(
+            FileStatus::from_code(b'?', b'?'),
+            status(false, false, true, false)
+        );
+        assert_eq!(
+            FileStatus::from_code(b'D', b' '),
+            status(true, false, false, true)
+        );
+        assert_eq!(
+            FileStatus::from_code(b' ', b'D'),
+            status(false, false, false, true)
+        );
+        assert_eq!(
+            FileStatus::from_code(b'U', b'U'),
+            status(false, true, false, false)
+        );
+        assert_eq!(
+            FileStatus::from_code(b'R', b' '),
+            status(true, false, false, false)
+        );
+    }
+
+    #[test]
+    fn porcelain() {
+        let output = b"R  new.rs\0old.rs\0C  copy.rs\0original.rs\0?? dir/new file.rs\0\
+                       D  staged.rs\0 D deleted.rs\0UU conflict.rs\0 M src/lib.rs\0";
+        let files: Vec<(String, FileStatus)> = parse_status(output)
+            .into_iter()
+            .map(|(path, status)| (path.to_string_lossy().into_owned(), status))
+            .collect();
+
+        assert_eq!(
+            files,
+            [
+                ("new.rs".to_string(), status(true, false, false, false)),
+                ("copy.rs".to_string(), status(true, false, false, false)),
+                (
+                    "dir/new file.rs".to_string(),
+                    status(false, false, true, false)
+                ),
+                ("staged.rs".to_string(), status(true, false, false, true)),
+                ("deleted.rs".to_string(), status(false, false, false, true)),
+                ("conflict.rs".to_string(), status(false, true, false, false)),
+                ("src/lib.rs".to_string(), status(false, true, false, false)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,186 @@
+//! Content fingerprints of parts.
+//!
+//! The fingerprint of a part is the root of a Merkle tree built from
+//! its files: each file is hashed from its contents, and each directory
+//! from the names and hashes of its children, sorted by name. It is thus
+//! independent of the traversal order, and only depends on file paths
+//! (relative to the directory of the config file) and contents.
+use crate::walk::{absolute, relative_to};
+use sha2::{Digest as _, Sha256};
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// SHA-256 digest.
+pub type Digest = [u8; 32];
+
+/// Format a digest as a lowercase hexadecimal string.
+pub fn to_hex(digest: &Digest) -> String {
+    digest.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Hash the contents of a file.
+pub fn hash_file(path: &Path) -> std::io::Result<Digest> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0; 8192];
+
+    loop {
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+
+    Ok(hasher.finalize().into())
+}
+
+/// Merkle tree of a set of files.
+#[derive(Clone, Debug)]
+pub struct Tree {
+    /// Hash of every directory containing (possibly indirectly) a file,
+    /// relative to the root of the tree, the empty path being the root.
+    pub directories: BTreeMap<PathBuf, Digest>,
+}
+
+impl Tree {
+    /// Hash all the given files, and build the corresponding tree.
+    ///
+    /// Paths are relative to the current directory, and are usually
+    /// obtained from [`Walker::collect`](crate::walk::Walker::collect).
+    /// They are hashed relative to `root` (usually the directory of the
+    /// config file), so that the tree does not depend on the current
+    /// directory.
+    pub fn new(paths: &[PathBuf], root: &Path) -> std::io::Result<Self> {
+        let root = absolute(root);
+        // For each directory, its children as (name, is_dir, digest)
+        let mut children: BTreeMap<PathBuf, Vec<(String, bool, Digest)>> = BTreeMap::new();
+        children.insert(PathBuf::new(), vec![]);
+
+        for path in paths.iter() {
+            let digest = hash_file(path)?;
+            let path = relative_to(&absolute(path), &root);
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let parent = path.parent().unwrap_or_else(|| Path::new(""));
+
+            children.entry(parent.to_path_buf()).or_default().push((
+                name.into_owned(),
+                false,
+                digest,
+            ));
+
+            for ancestor in parent.ancestors().skip(1) {
+                children.entry(ancestor.to_path_buf()).or_default();
+            }
+        }
+
+        let mut directories = BTreeMap::new();
+        let mut paths: Vec<PathBuf> = children.keys().cloned().collect();
+
+        // Deepest directories first, so that children are hashed before parents
+        paths.sort_by_key(|path| std::cmp::Reverse(path.components().count()));
+
+        for path in paths {
+            let mut entries = children.remove(&path).unwrap_or_default();
+            entries.sort();
+
+            let mut hasher = Sha256::new();
+            for (name, is_dir, digest) in entries.iter() {
+                hasher.update([*is_dir as u8]);
+                hasher.update(name.as_bytes());
+                hasher.update([0]);
+                hasher.update(digest);
+            }
+            let digest: Digest = hasher.finalize().into();
+
+            if let Some(parent) = path.parent() {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                children.entry(parent.to_path_buf()).or_default().push((
+                    name.into_owned(),
+                    true,
+                    digest,
+                ));
+            }
+
+            directories.insert(path, digest);
+        }
+
+        Ok(Tree { directories })
+    }
+
+    /// Hash of the whole tree.
+    pub fn root(&self) -> Digest {
+        self.directories[Path::new("")]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Create the given files in a fresh temporary directory, and return
+    /// their absolute paths.
+    fn files(name: &str, files: &[(&str, &str)]) -> (PathBuf, Vec<PathBuf>) {
+        let root = std::env::temp_dir()
+            .join("parts-tests")
+            .join(format!("{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+
+        let paths = files
+            .iter()
+            .map(|(path, content)| {
+                let path = root.join(path);
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::write(&path, content).unwrap();
+                path
+            })
+            .collect();
+        (root, paths)
+    }
+
+    fn root_hash(paths: &[PathBuf], root: &Path) -> Digest {
+        Tree::new(paths, root).unwrap().root()
+    }
+
+    #[test]
+    fn fingerprint() {
+        let project = [
+            ("src/lib.rs", "lib"),
+            ("src/a/b.rs", "b"),
+            ("README.md", ""),
+        ];
+        let (root, mut paths) = files("fingerprint", &project);
+        let digest = root_hash(&paths, &root);
+
+        // Independent of the order of the paths
+        paths.reverse();
+        assert_eq!(root_hash(&paths, &root), digest);
+
+        // Independent of the current directory, i.e., of how paths are written
+        let current_dir = std::env::current_dir().unwrap();
+        let relative: Vec<PathBuf> = paths
+            .iter()
+            .map(|path| relative_to(path, &current_dir))
+            .collect();
+        assert_eq!(root_hash(&relative, &root), digest);
+
+        // Independent of the location of the project
+        let (other_root, other_paths) = files("fingerprint-copy", &project);
+        assert_eq!(root_hash(&other_paths, &other_root), digest);
+
+        // Dependent on the contents and paths of the files
+        std::fs::write(root.join("README.md"), "changed").unwrap();
+        assert_ne!(root_hash(&paths, &root), digest);
+
+        let (root, paths) = files(
+            "fingerprint-renamed",
+            &[
+                ("src/lib.rs", "lib"),
+                ("src/a/c.rs", "b"),
+                ("README.md", ""),
+            ],
+        );
+        assert_ne!(root_hash(&paths, &root), digest);
+    }
+}
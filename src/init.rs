@@ -0,0 +1,347 @@
+//! Scaffolding of config files.
+//!
+//! [`suggest_parts`] inspects the layout of the project in the current
+//! directory (Cargo workspace members, Python packages, npm workspaces,
+//! documentation, GitHub workflows and tests directories), and
+//! [`render`] turns the suggested parts into a config file.
+use crate::config::{
+    expand_directory, split_path_and_keys, try_parse_config_str, POSSIBLE_CONFIG_PATHS,
+};
+use crate::error::{Error, Result};
+use crate::walk::{absolute, relative_to};
+use log::warn;
+use serde::Serialize;
+use std::path::Path;
+
+/// Part suggested for a project.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct PartSuggestion {
+    /// Name of the part.
+    #[serde(skip)]
+    pub name: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub directories: Vec<String>,
+    pub globs: Vec<String>,
+    #[serde(skip_serializing_if = "is_true")]
+    pub ignore_hidden: bool,
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+impl PartSuggestion {
+    fn new(name: &str, directories: Vec<String>, globs: &[&str]) -> Self {
+        PartSuggestion {
+            name: name.to_string(),
+            directories,
+            globs: globs.iter().map(|glob| glob.to_string()).collect(),
+            ignore_hidden: true,
+        }
+    }
+}
+
+/// Read a TOML manifest, if it exists and is valid.
+fn read_toml(path: &str) -> Option<toml::Table> {
+    let content = std::fs::read_to_string(path).ok()?;
+
+    match content.parse() {
+        Ok(table) => Some(table),
+        Err(err) => {
+            warn!("{path}: {err}");
+            None
+        }
+    }
+}
+
+/// Read a JSON manifest, if it exists and is valid.
+fn read_json(path: &str) -> Option<serde_json::Value> {
+    let content = std::fs::read_to_string(path).ok()?;
+
+    match serde_json::from_str(&content) {
+        Ok(value) => Some(value),
+        Err(err) => {
+            warn!("{path}: {err}");
+            None
+        }
+    }
+}
+
+/// Return the value at a path of keys in a TOML table.
+fn toml_get<'a>(table: &'a toml::Table, keys: &[&str]) -> Option<&'a toml::Value> {
+    let (key, keys) = keys.split_first()?;
+
+    keys.iter()
+        .try_fold(table.get(*key)?, |value, key| value.get(key))
+}
+
+/// Return the strings of an array, ignoring other values.
+fn toml_strings(value: Option<&toml::Value>) -> Vec<&str> {
+    value
+        .and_then(toml::Value::as_array)
+        .map(|array| array.iter().filter_map(toml::Value::as_str).collect())
+        .unwrap_or_default()
+}
+
+/// Expand workspace member patterns into existing directories, relative
+/// to the current directory.
+fn expand_members<'a, I: IntoIterator<Item = &'a str>>(patterns: I) -> Vec<String> {
+    let current_dir = absolute(Path::new(""));
+
+    patterns
+        .into_iter()
+        .flat_map(|pattern| expand_directory(Path::new(""), pattern))
+        .filter(|directory| directory.is_dir() && *directory != current_dir)
+        .map(|directory| {
+            relative_to(&directory, &current_dir)
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect()
+}
+
+/// Return the name of the last component of a directory.
+fn directory_name(directory: &str) -> &str {
+    directory.rsplit('/').next().unwrap_or(directory)
+}
+
+fn cargo_parts(parts: &mut Vec<PartSuggestion>) {
+    let manifest = match read_toml("Cargo.toml") {
+        Some(manifest) => manifest,
+        None => return,
+    };
+
+    if let Some(name) = toml_get(&manifest, &["package", "name"]).and_then(toml::Value::as_str) {
+        parts.push(PartSuggestion::new(
+            name,
+            vec![],
+            &["src/**/*.rs", "build.rs", "Cargo.toml"],
+        ));
+    }
+
+    for member in expand_members(toml_strings(toml_get(&manifest, &["workspace", "members"]))) {
+        let name = read_toml(&format!("{member}/Cargo.toml"))
+            .and_then(|manifest| {
+                toml_get(&manifest, &["package", "name"])
+                    .and_then(toml::Value::as_str)
+                    .map(String::from)
+            })
+            .unwrap_or_else(|| directory_name(&member).to_string());
+        parts.push(PartSuggestion::new(
+            &name,
+            vec![member],
+            &["**/*.rs", "**/Cargo.toml"],
+        ));
+    }
+}
+
+fn python_parts(parts: &mut Vec<PartSuggestion>) {
+    let manifest = match read_toml("pyproject.toml") {
+        Some(manifest) => manifest,
+        None => return,
+    };
+    let mut packages = toml_strings(toml_get(&manifest, &["tool", "setuptools", "packages"]));
+
+    if packages.is_empty() {
+        packages.extend(
+            [&["project", "name"][..], &["tool", "poetry", "name"]]
+                .iter()
+                .filter_map(|keys| toml_get(&manifest, keys).and_then(toml::Value::as_str))
+                .take(1),
+        );
+    }
+
+    for package in packages {
+        let module = package.replace('-', "_").to_lowercase();
+        let directory = [format!("src/{module}"), module]
+            .into_iter()
+            .find(|directory| Path::new(directory).is_dir());
+
+        if let Some(directory) = directory {
+            parts.push(PartSuggestion::new(package, vec![directory], &["**/*.py"]));
+        }
+    }
+}
+
+fn npm_parts(parts: &mut Vec<PartSuggestion>) {
+    let manifest = match read_json("package.json") {
+        Some(manifest) => manifest,
+        None => return,
+    };
+    let workspaces = match &manifest["workspaces"] {
+        serde_json::Value::Object(workspaces) => &workspaces["packages"],
+        workspaces => workspaces,
+    };
+    let patterns = workspaces
+        .as_array()
+        .map(|array| array.iter().filter_map(serde_json::Value::as_str).collect())
+        .unwrap_or_else(Vec::new);
+
+    for member in expand_members(patterns) {
+        let name = read_json(&format!("{member}/package.json"))
+            .and_then(|manifest| manifest["name"].as_str().map(String::from))
+            .unwrap_or_else(|| directory_name(&member).to_string());
+        parts.push(PartSuggestion::new(
+            &name,
+            vec![member],
+            &["**/*.{js,jsx,mjs,cjs,ts,tsx}", "**/package.json"],
+        ));
+    }
+}
+
+/// Add a part with the directories of a list that exist, if any.
+fn directories_part(parts: &mut Vec<PartSuggestion>, name: &str, candidates: &[&str]) {
+    let directories: Vec<String> = candidates
+        .iter()
+        .filter(|directory| Path::new(directory).is_dir())
+        .map(|directory| directory.to_string())
+        .collect();
+
+    if !directories.is_empty() {
+        parts.push(PartSuggestion::new(name, directories, &["**"]));
+    }
+}
+
+/// Suggest parts for the project in the current directory.
+///
+/// Parts are suggested for the package and the workspace members of a
+/// `Cargo.toml`, the Python packages of a `pyproject.toml`, the
+/// workspaces of a `package.json`, as well as for the `docs`, tests and
+/// `.github/workflows` directories. If nothing is detected, a single part
+/// with all files is suggested.
+///
+/// Part names are unique: if two parts have the same name, the latter is
+/// named after its directory instead.
+pub fn suggest_parts() -> Vec<PartSuggestion> {
+    let mut candidates = vec![];
+
+    cargo_parts(&mut candidates);
+    python_parts(&mut candidates);
+    npm_parts(&mut candidates);
+    directories_part(&mut candidates, "docs", &["docs", "doc"]);
+    directories_part(&mut candidates, "tests", &["tests", "test"]);
+
+    if Path::new(".github/workflows").is_dir() {
+        candidates.push(PartSuggestion {
+            ignore_hidden: false,
+            ..PartSuggestion::new(
+                "workflows",
+                vec![],
+                &[".github/workflows/*.yml", ".github/workflows/*.yaml"],
+            )
+        });
+    }
+
+    if candidates.is_empty() {
+        candidates.push(PartSuggestion::new("all", vec![], &["**"]));
+    }
+
+    let mut parts: Vec<PartSuggestion> = vec![];
+
+    for mut part in candidates {
+        if parts.iter().any(|other| other.name == part.name) {
+            match part.directories.first() {
+                Some(directory) => part.name = directory.clone(),
+                None => continue,
+            }
+        }
+        if parts.iter().all(|other| other.name != part.name) {
+            parts.push(part);
+        }
+    }
+
+    parts
+}
+
+/// Render parts as a TOML config file, nested under a path of keys, the
+/// first part being the default one.
+///
+/// # Examples
+///
+/// ```
+/// # use parts::init::{render, suggest_parts};
+/// let config = render(&suggest_parts(), &["tool", "parts"]).unwrap();
+/// assert!(config.starts_with("[tool.parts]\ndefault = "));
+/// ```
+pub fn render(parts: &[PartSuggestion], keys: &[&str]) -> Result<String> {
+    let mut table = toml::Table::new();
+
+    if let Some(part) = parts.first() {
+        table.insert("default".to_string(), part.name.clone().into());
+    }
+    for part in parts.iter() {
+        table.insert(part.name.clone(), toml::Value::try_from(part)?);
+    }
+    for key in keys.iter().rev() {
+        let mut outer = toml::Table::new();
+        outer.insert(key.to_string(), table.into());
+        table = outer;
+    }
+
+    Ok(toml::to_string(&table)?)
+}
+
+/// Return the `"<path>(:<keys>)"` values a config can be written to, i.e.,
+/// the default locations of TOML config files.
+pub fn outputs() -> Vec<&'static str> {
+    POSSIBLE_CONFIG_PATHS
+        .iter()
+        .filter(|s| split_path_and_keys(s).0.ends_with(".toml"))
+        .copied()
+        .collect()
+}
+
+pub fn validate_output_value(value: &str) -> Result<String> {
+    if outputs().contains(&value) {
+        Ok(value.to_string())
+    } else {
+        Err(Error::UnsupportedInitOutput {
+            value: value.to_string(),
+            available: outputs().join(", "),
+        })
+    }
+}
+
+/// Write a rendered config to a `"<path>(:<keys>)"` value, once checked
+/// that it can be parsed back.
+///
+/// Standalone config files are created, and must not exist. Configs
+/// embedded under keys are appended to the file, which must not contain
+/// the keys already. Nothing is written if the resulting file is invalid.
+pub fn write_config(value: &str, config: &str) -> Result<()> {
+    let (path, keys) = split_path_and_keys(value);
+    let already_exists = || Error::ConfigAlreadyExists {
+        value: value.to_string(),
+    };
+
+    let content = if keys.is_empty() {
+        if Path::new(path).exists() {
+            return Err(already_exists());
+        }
+        config.to_string()
+    } else {
+        let mut content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err.into()),
+        };
+        let manifest: toml::Table = content.parse()?;
+
+        if toml_get(&manifest, &keys).is_some() {
+            return Err(already_exists());
+        }
+        if !content.is_empty() && !content.ends_with("\n\n") {
+            content.push_str(if content.ends_with('\n') {
+                "\n"
+            } else {
+                "\n\n"
+            });
+        }
+        content.push_str(config);
+        content
+    };
+
+    try_parse_config_str(path, &content, keys)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
@@ -0,0 +1,313 @@
+//! Interpolation of variables in config values.
+//!
+//! Directories and patterns of parts may contain the following placeholders:
+//!
+//! - `${VAR}`: the value of the environment variable `VAR`, which must be
+//!   set, possibly to an empty value;
+//! - `${VAR:-default}`: the value of `VAR`, or `default` if it is not set
+//!   or empty, where `default` may itself contain placeholders;
+//! - `${target_triple}`: the target triple of the build, i.e., the value
+//!   of `CARGO_BUILD_TARGET` if it is set, or else the triple `parts` itself
+//!   was compiled for (usually the host, e.g., `x86_64-unknown-linux-gnu`).
+//!
+//! Directories (but not patterns, which are matched against paths relative
+//! to the directory of the config file) may also contain:
+//!
+//! - `${config_dir}`: the absolute path of the directory of the config file;
+//! - `${git_root}`: the absolute path of the git repository containing the
+//!   config file.
+//!
+//! Built-in variables take precedence over environment variables, and
+//! `$${` is written as a literal `${`. Values are inserted as-is, i.e.,
+//! they are not escaped in globs and regexes.
+use crate::error::{Error, Result};
+use crate::walk::absolute;
+use std::cell::RefCell;
+use std::path::PathBuf;
+
+/// Target triple `parts` was compiled for, see `build.rs`.
+const TARGET_TRIPLE: &str = env!("TARGET");
+
+/// Context in which built-in variables are resolved.
+#[derive(Clone, Debug, Default)]
+pub struct Variables {
+    /// Directory of the config file, relative to the current directory.
+    pub config_dir: PathBuf,
+    /// Whether the value is a directory, in which `${config_dir}` and
+    /// `${git_root}` are available.
+    pub directory: bool,
+}
+
+impl Variables {
+    /// Return the value of a variable, if it is defined.
+    fn get(&self, name: &str) -> Result<Option<String>> {
+        let config_dir = absolute(&self.config_dir);
+
+        let value = match name {
+            "config_dir" | "git_root" if !self.directory => {
+                return Err(Error::Interpolation {
+                    value: format!("${{{name}}}"),
+                    reason: "it is only available in directories, as patterns are relative \
+                             to the directory of the config file"
+                        .to_string(),
+                })
+            }
+            "config_dir" => Some(config_dir.to_string_lossy().into_owned()),
+            "git_root" => {
+                let git_root = config_dir
+                    .ancestors()
+                    .find(|directory| directory.join(".git").exists())
+                    .ok_or_else(|| Error::Interpolation {
+                        value: format!("${{{name}}}"),
+                        reason: "the config file is not in a git repository".to_string(),
+                    })?;
+                Some(git_root.to_string_lossy().into_owned())
+            }
+            "target_triple" => Some(
+                std::env::var("CARGO_BUILD_TARGET")
+                    .ok()
+                    .filter(|value| !value.is_empty())
+                    .unwrap_or_else(|| TARGET_TRIPLE.to_string()),
+            ),
+            name => std::env::var(name).ok(),
+        };
+
+        Ok(value)
+    }
+}
+
+thread_local! {
+    static VARIABLES: RefCell<Variables> = RefCell::new(Variables::default());
+}
+
+/// Guard restoring the previous variables when dropped, i.e., even if the
+/// function run by [`with_variables`] panics.
+struct Restore(Option<Variables>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        if let Some(previous) = self.0.take() {
+            VARIABLES.with(|current| current.replace(previous));
+        }
+    }
+}
+
+/// Run a function with the variables used by [`interpolate_current`].
+pub fn with_variables<T, F: FnOnce() -> T>(variables: Variables, f: F) -> T {
+    let _restore = Restore(Some(VARIABLES.with(|current| current.replace(variables))));
+    f()
+}
+
+/// Interpolate a value with the variables set by [`with_variables`],
+/// `directory` telling whether the value is a directory (see
+/// [`Variables::directory`]).
+pub fn interpolate_current(value: &str, directory: bool) -> Result<String> {
+    VARIABLES.with(|variables| {
+        let variables = Variables {
+            directory,
+            ..variables.borrow().clone()
+        };
+        interpolate(value, &variables)
+    })
+}
+
+/// Return the length of a placeholder's contents, i.e., up to the matching
+/// closing brace, if any.
+fn placeholder_len(s: &str) -> Option<usize> {
+    let mut depth = 0;
+
+    for (index, c) in s.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(index),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Replace the placeholders of a value by the values of their variables.
+///
+/// # Examples
+///
+/// ```
+/// # use parts::interpolate::{interpolate, Variables};
+/// # use std::path::PathBuf;
+/// let variables = Variables {
+///     config_dir: PathBuf::from("/project"),
+///     directory: true,
+/// };
+/// let value = interpolate("${config_dir}/${PARTS_UNSET:-target}/*", &variables).unwrap();
+/// assert_eq!(value, "/project/target/*");
+/// assert_eq!(interpolate("$${HOME}", &variables).unwrap(), "${HOME}");
+/// assert!(interpolate("${PARTS_UNSET}", &variables).is_err());
+/// ```
+pub fn interpolate(value: &str, variables: &Variables) -> Result<String> {
+    let invalid = |reason: String| Error::Interpolation {
+        value: value.to_string(),
+        reason,
+    };
+    let mut interpolated = String::with_capacity(value.len());
+    let mut rest = value;
+
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            interpolated.push_str(&rest[..start - 1]);
+            interpolated.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+
+        interpolated.push_str(&rest[..start]);
+        rest = &rest[start + 2..];
+
+        let len = placeholder_len(rest)
+            .ok_or_else(|| invalid("unclosed '${', use '$${' to escape it".to_string()))?;
+        let (placeholder, after) = (&rest[..len], &rest[len + 1..]);
+        let (name, default) = match placeholder.split_once(":-") {
+            Some((name, default)) => (name.trim(), Some(default)),
+            None => (placeholder.trim(), None),
+        };
+
+        if name.is_empty() {
+            return Err(invalid("empty variable name".to_string()));
+        }
+
+        // As in shells, only `:-` replaces empty values
+        let value = variables
+            .get(name)?
+            .filter(|value| default.is_none() || !value.is_empty());
+
+        match (value, default) {
+            (Some(value), _) => interpolated.push_str(&value),
+            (None, Some(default)) => interpolated.push_str(&interpolate(default, variables)?),
+            (None, None) => {
+                return Err(invalid(format!(
+                    "environment variable {name:?} is not set, use '${{{name}:-default}}' \
+                     to provide a default value"
+                )))
+            }
+        }
+        rest = after;
+    }
+
+    interpolated.push_str(rest);
+    Ok(interpolated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables(directory: bool) -> Variables {
+        Variables {
+            config_dir: PathBuf::from("/project"),
+            directory,
+        }
+    }
+
+    fn interpolate_pattern(value: &str) -> Result<String> {
+        interpolate(value, &variables(false))
+    }
+
+    #[test]
+    fn defaults() {
+        std::env::set_var("PARTS_TEST_DEFAULTS_SET", "set");
+        std::env::set_var("PARTS_TEST_DEFAULTS_EMPTY", "");
+
+        assert_eq!(
+            interpolate_pattern("${PARTS_TEST_DEFAULTS_SET:-default}").unwrap(),
+            "set"
+        );
+        assert_eq!(
+            interpolate_pattern("${PARTS_TEST_DEFAULTS_EMPTY:-default}").unwrap(),
+            "default"
+        );
+        assert_eq!(
+            interpolate_pattern("${PARTS_TEST_DEFAULTS_UNSET:-default}/*").unwrap(),
+            "default/*"
+        );
+        assert_eq!(
+            interpolate_pattern("${PARTS_TEST_DEFAULTS_UNSET:-}").unwrap(),
+            ""
+        );
+    }
+
+    #[test]
+    fn unset() {
+        std::env::set_var("PARTS_TEST_UNSET_EMPTY", "");
+
+        assert!(matches!(
+            interpolate_pattern("${PARTS_TEST_UNSET}"),
+            Err(Error::Interpolation { .. })
+        ));
+        // Empty variables are set
+        assert_eq!(
+            interpolate_pattern("a/${PARTS_TEST_UNSET_EMPTY}/b").unwrap(),
+            "a//b"
+        );
+    }
+
+    #[test]
+    fn escaping() {
+        assert_eq!(interpolate_pattern("$${HOME}").unwrap(), "${HOME}");
+        assert_eq!(interpolate_pattern("a$${b}c").unwrap(), "a${b}c");
+        assert_eq!(interpolate_pattern("$x{y} ^a$").unwrap(), "$x{y} ^a$");
+        assert_eq!(
+            interpolate_pattern("${PARTS_TEST_ESCAPING_UNSET:-$${x}}").unwrap(),
+            "${x}"
+        );
+
+        for value in ["${", "a/${HOME", "${}", "${ :-x}"] {
+            assert!(
+                matches!(interpolate_pattern(value), Err(Error::Interpolation { .. })),
+                "{value:?} should not be interpolated"
+            );
+        }
+    }
+
+    #[test]
+    fn nested_braces() {
+        std::env::set_var("PARTS_TEST_NESTED_SET", "set");
+
+        assert_eq!(
+            interpolate_pattern("${PARTS_TEST_NESTED_UNSET:-${PARTS_TEST_NESTED_SET}}/*").unwrap(),
+            "set/*"
+        );
+        assert_eq!(
+            interpolate_pattern("${PARTS_TEST_NESTED_UNSET:-${PARTS_TEST_NESTED_UNSET:-default}}")
+                .unwrap(),
+            "default"
+        );
+        assert_eq!(
+            interpolate_pattern("${PARTS_TEST_NESTED_UNSET:-*.{rs,toml}}").unwrap(),
+            "*.{rs,toml}"
+        );
+        assert!(
+            interpolate_pattern("${PARTS_TEST_NESTED_UNSET:-${PARTS_TEST_NESTED_UNSET}}").is_err()
+        );
+    }
+
+    #[test]
+    fn built_ins() {
+        assert_eq!(
+            interpolate("${config_dir}/out", &variables(true)).unwrap(),
+            "/project/out"
+        );
+        assert!(interpolate_pattern("${config_dir}/out").is_err());
+        assert!(interpolate_pattern("${git_root}/out").is_err());
+        assert!(!interpolate_pattern("${target_triple}").unwrap().is_empty());
+    }
+
+    #[test]
+    fn restore_variables() {
+        let result = std::panic::catch_unwind(|| {
+            with_variables(variables(false), || panic!("interpolation failed"))
+        });
+
+        assert!(result.is_err());
+        VARIABLES.with(|current| assert_eq!(current.borrow().config_dir, PathBuf::new()));
+    }
+}
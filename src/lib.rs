@@ -0,0 +1,47 @@
+//! Divide your projects into multiple (possibly overlapping) parts,
+//! allowing to monitor changes of user-defined sections within a larger
+//! project.
+//!
+//! This crate is primarily used through the `parts` command-line tool,
+//! but it also exposes its configuration and traversal logic, so that
+//! other tools can query parts in-process.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # fn main() -> parts::Result<()> {
+//! // Look for a config file in the default locations
+//! let config_file = parts::load_config_file(None)?;
+//!
+//! for name in config_file.part_names() {
+//!     let walker = config_file.walker(name)?;
+//!     println!("{name}: {} files", walker.collect().len());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! # Features
+//!
+//! - `git`: integration with git, see the [`git`] module.
+//! - `monitor`: filesystem monitoring, see the [`monitor`] module.
+pub mod check;
+pub mod config;
+pub mod diagnostic;
+pub mod error;
+pub mod expr;
+#[cfg(feature = "git")]
+pub mod git;
+pub mod hash;
+pub mod init;
+pub mod interpolate;
+#[cfg(feature = "monitor")]
+pub mod monitor;
+pub mod output;
+pub mod snapshot;
+pub mod walk;
+
+pub use config::{load_config_file, try_find_config_file, try_parse_config_file};
+pub use config::{Config, ConfigFile};
+pub use error::{Error, Result};
+pub use walk::Walker;
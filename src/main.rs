@@ -1,15 +1,24 @@
 #[cfg(feature = "clap_complete")]
 use clap_complete::{generate, shells};
 
+#[cfg(feature = "clap_complete")]
 use clap::CommandFactory;
 use clap::Parser;
 use clap_verbosity_flag::Verbosity;
-use termcolor::{BufferWriter, ColorChoice, StandardStream};
+use itertools::Itertools;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use termcolor::{BufferWriter, Color, ColorChoice, StandardStream};
+use termcolor::{ColorSpec, WriteColor};
 
-mod config;
-mod error;
-mod walk;
-use error::{Error, Result};
+#[cfg(feature = "git")]
+use parts::git;
+#[cfg(feature = "monitor")]
+use parts::monitor;
+use parts::output::{Format, RecordWriter, Value};
+use parts::{check, config, hash, init, snapshot, walk};
+use parts::{Error, Result};
 
 #[derive(Parser)]
 #[command(about)]
@@ -17,7 +26,8 @@ use error::{Error, Result};
 #[command(version)]
 struct Cli {
     #[clap(short, long, value_parser = config::validate_config_file_value)]
-    /// Config file path, with optional keys. Must be an existing .toml file.
+    /// Config file path, with optional keys. Must be an existing TOML, YAML
+    /// (.yaml or .yml) or JSON (.json) file.
     ///
     /// If the config is included in some parts of a bigger config, indicate
     /// it with keys.
@@ -25,6 +35,26 @@ struct Cli {
     /// The expected format is "<path>:(<keys>)+", where keys are separated
     /// with a dot `.` (dot not trailing dot at the end).
     config: Option<String>,
+    /// Output format of the commands.
+    ///
+    /// Possible values are "text" (the default), "json", "ndjson",
+    /// "nul" (NUL-separated, e.g., for `xargs -0`), or a template where
+    /// `{field}` placeholders are replaced by their values,
+    /// e.g., "{path}\t{size}\t{part}". `complete`, `explain`, `init`,
+    /// `overlap --matrix` and `watch --exec` only support "text", and
+    /// `watch` does not support "json", as events never end.
+    ///
+    /// Available fields are "name", "default" and "directories" for `list`,
+    /// "path", "size" and "part" for `walk`, "hash" and "path" for `hash`,
+    /// "a", "b", "shared" and "files" (with `--list`) for `overlap`, "path"
+    /// for `coverage`, "path" and "parts" for `which`, "severity", "part",
+    /// "key" and "message" for `check`, "part" and "files" (with `--files`)
+    /// for `changed`, "part", "modified", "staged", "untracked" and
+    /// "deleted" for `status`, "part" and "files" for `snapshot save`,
+    /// "path", "part" and "change" for `snapshot diff`, and "path", "kind"
+    /// and "parts" for `watch`.
+    #[clap(long, global = true, default_value = "text", value_parser = Format::from_str)]
+    format: Format,
     #[clap(flatten)]
     verbose: Verbosity,
     #[command(subcommand)]
@@ -122,13 +152,32 @@ struct CompleteCommand {
 /// List all parts specified in a given config file.
 struct ListCommand {}
 
+#[derive(Parser)]
+/// Compute a fingerprint of all files in given part.
+///
+/// The fingerprint is a SHA-256 hash that depends on file paths (relative
+/// to the directory of the config file) and contents, but not on the
+/// traversal order or the current directory, which makes it suitable as
+/// a cache key.
+struct HashCommand {
+    /// Part name, as defined in the config file, or set expression of
+    /// parts, e.g., "src & !tests", "docs | examples" or "(a | b) - c".
+    part: String,
+
+    /// If true, will also print the hash of every directory, relative to
+    /// the directory of the config file.
+    #[clap(short, long, default_value = "false")]
+    tree: bool,
+}
+
 #[derive(Parser)]
 /// Walk through all files in given part, and print them.
 ///
 /// As the traversal is performed in parallel, the output
 /// order is not deterministic.
 struct WalkCommand {
-    /// Part name, as defined in the config file.
+    /// Part name, as defined in the config file, or set expression of
+    /// parts, e.g., "src & !tests", "docs | examples" or "(a | b) - c".
     part: String,
 
     /// If true, will sort files by names.
@@ -136,85 +185,976 @@ struct WalkCommand {
     /// This may dramatically decrease the performances.
     #[clap(short, long, default_value = "false")]
     sorted: bool,
+
+    /// If true, print paths relative to the directory of the config file,
+    /// instead of the current directory.
+    #[clap(long, default_value = "false")]
+    root_relative: bool,
+}
+
+#[derive(Parser)]
+/// Check the config file for likely mistakes.
+///
+/// Reports a `default` part that does not exist, directories that do not
+/// exist, parts and patterns that match no file, exclude patterns that
+/// exclude nothing, suspicious globs, and part names that cannot be used
+/// in set expressions. Exits with an error if any
+/// error (or, with `--strict`, any warning) is found.
+struct CheckCommand {
+    /// If true, will also fail on warnings.
+    #[clap(long, default_value = "false")]
+    strict: bool,
+}
+
+#[derive(Parser)]
+/// Create a config file by inspecting the project in the current directory.
+///
+/// Parts are suggested for the Cargo package and workspace members, the
+/// Python packages of `pyproject.toml`, the npm workspaces, and the docs,
+/// tests and GitHub workflows directories. The first part is the default
+/// one.
+struct InitCommand {
+    /// Where to write the config, as "<path>(:<keys>)". Possible values are
+    /// "parts.toml", ".parts.toml", "Cargo.toml:metadata.parts" and
+    /// "pyproject.toml:tool.parts".
+    #[clap(long, default_value = ".parts.toml", value_parser = parts::init::validate_output_value)]
+    output: String,
+    /// If true, will print the config instead of writing it.
+    #[clap(long, default_value = "false")]
+    dry_run: bool,
+}
+
+#[derive(Parser)]
+/// List files in the project that belong to no part.
+///
+/// All files in the directory of the config file are walked, skipping
+/// hidden and ignored files unless specified otherwise, and those that do
+/// not belong to any part are printed, followed by a summary.
+struct CoverageCommand {
+    /// If true, will also consider hidden files.
+    #[clap(long, default_value = "false")]
+    hidden: bool,
+
+    /// If true, will also consider files ignored by `.gitignore` rules.
+    #[clap(long, default_value = "false")]
+    no_ignore: bool,
+
+    /// Exit with an error if the percentage of files belonging to some part
+    /// is lower than this value. Use 100 to require that every file
+    /// belongs to some part.
+    #[clap(long, value_name = "PERCENT")]
+    fail_under: Option<f64>,
+}
+
+#[derive(Parser)]
+/// Explain why a file belongs, or does not belong, to a part.
+///
+/// Reports whether the file is inside the part's directory, whether it is
+/// skipped as a hidden or ignored file before matching, and which include
+/// and exclude patterns (by key and index in the config file) match it.
+struct ExplainCommand {
+    /// Part name, as defined in the config file.
+    part: String,
+
+    /// File path, relative to the current directory or absolute.
+    path: PathBuf,
+}
+
+#[cfg(feature = "git")]
+#[derive(Parser)]
+/// Print the parts affected by changes since a given git revision.
+///
+/// By default, the working tree (including untracked files) is compared
+/// against the revision. Parts are printed in alphabetical order, which
+/// makes the output suitable for selecting CI jobs to run.
+struct ChangedCommand {
+    /// Base revision to compare against.
+    #[clap(required_unless_present = "merge_base", conflicts_with = "merge_base")]
+    rev: Option<String>,
+
+    /// Compare against the merge base of this revision and HEAD.
+    #[clap(short, long, value_name = "REV")]
+    merge_base: Option<String>,
+
+    /// Only consider committed changes, i.e., compare against HEAD instead
+    /// of the working tree.
+    #[clap(long, default_value = "false")]
+    head: bool,
+
+    /// If true, will also print changed files below each part.
+    #[clap(short, long, default_value = "false")]
+    files: bool,
+}
+
+#[derive(Parser)]
+/// Report the number of files shared by every pair of parts.
+///
+/// Files of all parts are found in a single traversal. Pairs are printed
+/// as a table, or as a matrix whose diagonal contains the number of files
+/// in each part.
+struct OverlapCommand {
+    /// Part names, as defined in the config file, or set expressions of
+    /// parts (see `walk --help`). If not specified, all parts are compared.
+    parts: Vec<String>,
+
+    /// If true, will also list the files shared by each pair.
+    #[clap(short, long, default_value = "false")]
+    list: bool,
+
+    /// If true, will print a matrix instead of a table.
+    #[clap(short, long, default_value = "false", conflicts_with = "list")]
+    matrix: bool,
+}
+
+#[derive(Parser)]
+/// Save and compare snapshots of parts.
+///
+/// Snapshots record the size, modification time and content hash of every
+/// file in a part, which allows to monitor changes in directories that are
+/// not managed by a VCS.
+struct SnapshotCommand {
+    /// Directory in which snapshots are stored, one file per part,
+    /// relative to the directory of the config file.
+    #[clap(long, env = "PARTS_STATE_DIR", default_value = ".parts/snapshots")]
+    state_dir: PathBuf,
+    #[command(subcommand)]
+    action: SnapshotAction,
+}
+
+#[derive(Parser)]
+/// Save a snapshot of the current files of one or more parts.
+struct SnapshotSaveCommand {
+    /// Part names, as defined in the config file, or set expressions of
+    /// parts (see `walk --help`). If not specified, all parts are saved.
+    parts: Vec<String>,
+}
+
+#[derive(Parser)]
+/// Compare the current files of one or more parts against their snapshots.
+struct SnapshotDiffCommand {
+    /// Part names, as defined in the config file, or set expressions of
+    /// parts (see `walk --help`). If not specified, all parts are compared.
+    parts: Vec<String>,
+}
+
+#[derive(clap::Subcommand)]
+enum SnapshotAction {
+    Save(SnapshotSaveCommand),
+    Diff(SnapshotDiffCommand),
+}
+
+#[cfg(feature = "git")]
+#[derive(Parser)]
+/// Show the working tree status of every part.
+///
+/// For each part, print the number of modified, staged, untracked
+/// and deleted files, as reported by `git status`.
+struct StatusCommand {
+    /// Part name, as defined in the config file, or set expression of
+    /// parts (see `walk --help`). If not specified, all parts are shown.
+    part: Option<String>,
+}
+
+#[derive(Parser)]
+/// Print the parts that contain the given files.
+///
+/// Only the include and exclude rules of the parts (and whether they skip
+/// hidden files) are checked, so the filesystem is not walked and files
+/// do not need to exist. For each file, the path is printed, followed by
+/// a tab and the comma-separated names of the matching parts.
+struct WhichCommand {
+    /// File paths, relative to the current directory or absolute.
+    #[clap(required = true)]
+    paths: Vec<PathBuf>,
+}
+
+#[cfg(feature = "monitor")]
+#[derive(Parser)]
+/// Watch the files of one or more parts, and print filesystem events.
+///
+/// Each event is printed on one line, with its kind (created, modified,
+/// removed or renamed), the file path and the parts containing the file.
+///
+/// If a command is given with `--exec`, it is run instead each time the
+/// parts change. The changed files and parts are available, one per line,
+/// in the `PARTS_CHANGED_FILES` and `PARTS_CHANGED_PARTS` environment
+/// variables.
+struct WatchCommand {
+    /// Part names, as defined in the config file, or set expressions of
+    /// parts (see `walk --help`). If not specified, all parts are watched.
+    parts: Vec<String>,
+
+    /// Command to run, through the shell, when some part changes.
+    ///
+    /// If the command is still running when new changes occur,
+    /// it is killed and started again.
+    #[clap(short, long, value_name = "COMMAND")]
+    exec: Option<String>,
+
+    /// Delay, in milliseconds, during which no change must occur
+    /// before the command is run.
+    ///
+    /// Bursts of changes (e.g., during a `git checkout`) are coalesced
+    /// into a single run.
+    #[clap(short, long, default_value = "200", value_name = "MS")]
+    debounce: u64,
 }
 
 #[derive(clap::Subcommand)]
 enum Action {
     //Show(ShowCommand),
+    #[cfg(feature = "git")]
+    Changed(ChangedCommand),
+    Check(CheckCommand),
     #[cfg(feature = "clap_complete")]
     Complete(CompleteCommand),
+    Coverage(CoverageCommand),
+    Explain(ExplainCommand),
+    Hash(HashCommand),
+    Init(InitCommand),
     List(ListCommand),
+    Overlap(OverlapCommand),
+    Snapshot(SnapshotCommand),
+    #[cfg(feature = "git")]
+    Status(StatusCommand),
     Walk(WalkCommand),
+    #[cfg(feature = "monitor")]
+    Watch(WatchCommand),
+    Which(WhichCommand),
 }
 
-fn main() -> Result<()> {
+/// Return an error if a command that only prints text is given another
+/// output format.
+fn text_only(format: &Format, command: &str) -> Result<()> {
+    if *format == Format::Text {
+        Ok(())
+    } else {
+        Err(Error::UnsupportedFormat {
+            command: command.to_string(),
+            supported: "text".to_string(),
+        })
+    }
+}
+
+// Errors are returned as `anyhow::Error`, so that they are displayed rather
+// than debug-formatted, e.g., config errors with their location in the file
+fn main() -> anyhow::Result<()> {
     let cli = Cli::parse();
 
     pretty_env_logger::formatted_builder()
         .filter_level(cli.verbose.log_level_filter())
         .init();
 
-    let config_file = match &cli.config {
-        Some(config_file) => {
-            let (path, keys) = config::split_path_and_keys(config_file);
-            config::try_parse_config_file(path, keys)?
-        }
-        None => config::try_find_config_file()?,
-    };
-
     let choice = if atty::is(atty::Stream::Stdout) {
         ColorChoice::Auto
     } else {
         ColorChoice::Never
     };
-    let mut buffer_writer = BufferWriter::stdout(choice);
+    let buffer_writer = BufferWriter::stdout(choice);
     let mut stdout = StandardStream::stdout(choice);
 
+    // The config file does not exist yet when initializing it
+    let config_file = match cli.action {
+        Action::Init(_) => config::ConfigFile::default(),
+        _ => config::load_config_file(cli.config.as_deref())?,
+    };
+
     match cli.action {
         Action::List(_) => {
-            config_file.write_list(&mut stdout)?;
+            if cli.format == Format::Text {
+                config_file.write_list(&mut stdout)?;
+            } else {
+                let mut writer = RecordWriter::new(cli.format, &mut stdout);
+
+                for name in config_file.part_names() {
+                    let directories = config_file.configs[name].directory_patterns();
+                    writer.write(&[
+                        ("name", Value::Str(name)),
+                        ("default", Value::Bool(config_file.matches_default(name))),
+                        ("directories", Value::Strs(&directories)),
+                    ])?;
+                }
+                writer.finish()?;
+            }
         }
         Action::Walk(walk) => {
-            let config = config_file
-                .get(Some(&walk.part))
-                .ok_or(Error::UnknownPart { part: walk.part })?;
-            let walker: walk::Walker = config.clone().into();
-            walker.walk(&mut buffer_writer);
+            let walker = config_file.expression_walker(&walk.part)?;
+            let base = if walk.root_relative {
+                Some(config_file.root.as_path())
+            } else {
+                None
+            };
+            if cli.format == Format::Text {
+                walker.walk(&buffer_writer, walk.sorted, base)?;
+            } else {
+                let mut writer = RecordWriter::new(cli.format, &mut stdout);
+                let entries = if walk.sorted {
+                    walker.entries()
+                } else {
+                    walker.iter().collect()
+                };
+
+                let current_dir = walk::absolute(Path::new(""));
+
+                for entry in entries.iter() {
+                    let path = match base {
+                        Some(base) => {
+                            walk::relative_to(&walk::absolute_from(&current_dir, &entry.path), base)
+                        }
+                        None => entry.path.clone(),
+                    };
+                    writer.write(&[
+                        ("path", Value::Path(&path)),
+                        ("size", Value::Int(entry.metadata.len())),
+                        ("part", Value::Str(&walk.part)),
+                    ])?;
+                }
+                writer.finish()?;
+            }
+        }
+        Action::Hash(hash) => {
+            let walker = config_file.expression_walker(&hash.part)?;
+            let tree = hash::Tree::new(&walker.collect(), &config_file.root)?;
+
+            let directories: Vec<(&Path, &hash::Digest)> = tree
+                .directories
+                .iter()
+                .map(|(directory, digest)| {
+                    if directory.as_os_str().is_empty() {
+                        (Path::new("."), digest)
+                    } else {
+                        (directory.as_path(), digest)
+                    }
+                })
+                .collect();
+
+            if cli.format == Format::Text {
+                writeln!(stdout, "{}", hash::to_hex(&tree.root()))?;
+
+                if hash.tree {
+                    for (directory, digest) in directories {
+                        writeln!(stdout, "{}  {}/", hash::to_hex(digest), directory.display())?;
+                    }
+                }
+            } else {
+                let mut writer = RecordWriter::new(cli.format, &mut stdout);
+                let root = tree.root();
+                let directories = if hash.tree {
+                    directories
+                } else {
+                    vec![(Path::new("."), &root)]
+                };
+
+                for (directory, digest) in directories {
+                    writer.write(&[
+                        ("hash", Value::Str(&hash::to_hex(digest))),
+                        ("path", Value::Path(directory)),
+                    ])?;
+                }
+                writer.finish()?;
+            }
+        }
+        Action::Init(init) => {
+            text_only(&cli.format, "init")?;
+
+            let (_, keys) = config::split_path_and_keys(&init.output);
+            let suggestions = init::suggest_parts();
+            let config = init::render(&suggestions, &keys)?;
+
+            if init.dry_run {
+                write!(stdout, "{config}")?;
+            } else {
+                init::write_config(&init.output, &config)?;
+                writeln!(
+                    stdout,
+                    "Wrote {} part(s) to {}: {}",
+                    suggestions.len(),
+                    init.output,
+                    suggestions.iter().map(|part| &part.name).join(", ")
+                )?;
+            }
+        }
+        Action::Coverage(coverage) => {
+            let mut walkers = vec![walk::Walker::new(
+                walk::Matcher::directory(config_file.root.clone()),
+                !coverage.hidden,
+                !coverage.no_ignore,
+            )];
+            for name in config_file.part_names() {
+                walkers.push(config_file.walker(name)?);
+            }
+
+            let files = walk::classify(&walkers);
+            // Files found by the first walker, i.e., in the directory of the config file
+            let total = files.values().filter(|indices| indices[0] == 0).count();
+            let uncovered: Vec<PathBuf> = files
+                .into_iter()
+                .filter(|(_, indices)| indices == &[0])
+                .map(|(path, _)| path)
+                .collect();
+            let percent = if total == 0 {
+                100.0
+            } else {
+                100.0 * (total - uncovered.len()) as f64 / total as f64
+            };
+
+            if cli.format != Format::Text {
+                let mut writer = RecordWriter::new(cli.format, &mut stdout);
+
+                for path in uncovered.iter() {
+                    writer.write(&[("path", Value::Path(path))])?;
+                }
+                writer.finish()?;
+            } else {
+                for path in uncovered.iter() {
+                    writeln!(stdout, "{}", path.display())?;
+                }
+                writeln!(
+                    stdout,
+                    "{} of {} files ({:.1}%) belong to some part",
+                    total - uncovered.len(),
+                    total,
+                    percent
+                )?;
+            }
+
+            if let Some(required) = coverage.fail_under {
+                if percent < required {
+                    return Err(Error::InsufficientCoverage {
+                        uncovered: uncovered.len(),
+                        coverage: percent,
+                        required,
+                    }
+                    .into());
+                }
+            }
+        }
+        Action::Overlap(overlap) => {
+            if overlap.matrix {
+                text_only(&cli.format, "overlap --matrix")?;
+            }
+
+            let (names, walkers): (Vec<String>, Vec<walk::Walker>) =
+                config_file.walkers(&overlap.parts)?.into_iter().unzip();
+            let n = names.len();
+
+            // shared[i][j] contains the files in both parts i and j
+            let mut shared: Vec<Vec<Vec<PathBuf>>> = vec![vec![vec![]; n]; n];
+
+            for (path, indices) in walk::classify(&walkers) {
+                for &i in indices.iter() {
+                    for &j in indices.iter() {
+                        shared[i][j].push(path.clone());
+                    }
+                }
+            }
+
+            let pairs = (0..n).tuple_combinations::<(usize, usize)>();
+            let width = names.iter().map(String::len).max().unwrap_or_default();
+
+            if cli.format != Format::Text {
+                let mut writer = RecordWriter::new(cli.format, &mut stdout);
+
+                for (i, j) in pairs {
+                    let mut record = vec![
+                        ("a", Value::Str(&names[i])),
+                        ("b", Value::Str(&names[j])),
+                        ("shared", Value::Int(shared[i][j].len() as u64)),
+                    ];
+                    if overlap.list {
+                        record.push(("files", Value::Paths(&shared[i][j])));
+                    }
+                    writer.write(&record)?;
+                }
+                writer.finish()?;
+            } else if overlap.matrix {
+                let width = shared
+                    .iter()
+                    .flatten()
+                    .map(|files| files.len().to_string().len())
+                    .chain(std::iter::once(width))
+                    .max()
+                    .unwrap_or_default();
+
+                write!(stdout, "{:width$}", "")?;
+                for name in names.iter() {
+                    write!(stdout, "  {name:>width$}")?;
+                }
+                writeln!(stdout)?;
+
+                for (name, row) in names.iter().zip(shared.iter()) {
+                    write!(stdout, "{name:width$}")?;
+                    for files in row.iter() {
+                        write!(stdout, "  {:>width$}", files.len())?;
+                    }
+                    writeln!(stdout)?;
+                }
+            } else {
+                for (i, j) in pairs {
+                    writeln!(
+                        stdout,
+                        "{:width$}  {:width$}  {}",
+                        names[i],
+                        names[j],
+                        shared[i][j].len()
+                    )?;
+
+                    if overlap.list {
+                        for file in shared[i][j].iter() {
+                            writeln!(stdout, "  {}", file.display())?;
+                        }
+                    }
+                }
+            }
+        }
+        Action::Snapshot(snapshot) => {
+            let names = match &snapshot.action {
+                SnapshotAction::Save(save) => &save.parts,
+                SnapshotAction::Diff(diff) => &diff.parts,
+            };
+            // Number of files and, when comparing, differences of each part
+            let mut results = vec![];
+
+            for (name, walker) in config_file.walkers(names)? {
+                let path = config_file
+                    .root
+                    .join(&snapshot.state_dir)
+                    .join(snapshot::file_name(&config_file.expression(&name)?));
+                let files = walker.collect();
+
+                if matches!(snapshot.action, SnapshotAction::Diff(_)) && !path.exists() {
+                    return Err(Error::SnapshotNotFound {
+                        part: name,
+                        path: path.display().to_string(),
+                    }
+                    .into());
+                }
+
+                let diff = match snapshot.action {
+                    SnapshotAction::Save(_) => {
+                        snapshot::Snapshot::new(&files, &config_file.root)?.save(&path)?;
+                        None
+                    }
+                    SnapshotAction::Diff(_) => {
+                        Some(snapshot::Snapshot::load(&path)?.diff(&files, &config_file.root)?)
+                    }
+                };
+                results.push((name, files.len(), diff));
+            }
+
+            if cli.format == Format::Text {
+                let mut part_color = ColorSpec::new();
+                part_color.set_bold(true);
+
+                for (name, count, diff) in results {
+                    stdout.set_color(&part_color)?;
+                    write!(stdout, "{name}")?;
+                    stdout.reset()?;
+
+                    let diff = match diff {
+                        None => {
+                            writeln!(stdout, ": saved {count} files")?;
+                            continue;
+                        }
+                        Some(diff) if diff.is_empty() => {
+                            writeln!(stdout, ": clean")?;
+                            continue;
+                        }
+                        Some(diff) => diff,
+                    };
+
+                    writeln!(
+                        stdout,
+                        ": {} added, {} removed, {} modified",
+                        diff.added.len(),
+                        diff.removed.len(),
+                        diff.modified.len()
+                    )?;
+
+                    for (kind, files) in [
+                        ("added", &diff.added),
+                        ("removed", &diff.removed),
+                        ("modified", &diff.modified),
+                    ] {
+                        for file in files.iter() {
+                            writeln!(stdout, "  {kind}: {}", file.display())?;
+                        }
+                    }
+                }
+            } else {
+                let mut writer = RecordWriter::new(cli.format, &mut stdout);
+
+                for (name, count, diff) in results.iter() {
+                    let diff = match diff {
+                        None => {
+                            writer.write(&[
+                                ("part", Value::Str(name)),
+                                ("files", Value::Int(*count as u64)),
+                            ])?;
+                            continue;
+                        }
+                        Some(diff) => diff,
+                    };
+
+                    for (kind, files) in [
+                        ("added", &diff.added),
+                        ("removed", &diff.removed),
+                        ("modified", &diff.modified),
+                    ] {
+                        for file in files.iter() {
+                            writer.write(&[
+                                ("path", Value::Path(file)),
+                                ("part", Value::Str(name)),
+                                ("change", Value::Str(kind)),
+                            ])?;
+                        }
+                    }
+                }
+                writer.finish()?;
+            }
+        }
+        #[cfg(feature = "git")]
+        Action::Changed(changed) => {
+            let rev = match changed.merge_base {
+                Some(rev) => git::merge_base(&rev)?,
+                None => changed.rev.expect("clap requires either rev or merge base"),
+            };
+            let files = git::changed_files(&rev, changed.head)?;
+            let mut parts = vec![];
+
+            for name in config_file.part_names() {
+                let walker = config_file.walker(name)?;
+                let matched: Vec<PathBuf> = files
+                    .iter()
+                    .filter(|file| walker.is_match(file))
+                    .cloned()
+                    .collect();
+
+                if !matched.is_empty() {
+                    parts.push((name, matched));
+                }
+            }
+
+            if cli.format == Format::Text {
+                for (name, matched) in parts {
+                    writeln!(stdout, "{name}")?;
+
+                    if changed.files {
+                        for file in matched {
+                            writeln!(stdout, "  {}", file.display())?;
+                        }
+                    }
+                }
+            } else {
+                let mut writer = RecordWriter::new(cli.format, &mut stdout);
+
+                for (name, matched) in parts.iter() {
+                    let mut record = vec![("part", Value::Str(name))];
+                    if changed.files {
+                        record.push(("files", Value::Paths(matched)));
+                    }
+                    writer.write(&record)?;
+                }
+                writer.finish()?;
+            }
+        }
+        #[cfg(feature = "git")]
+        Action::Status(status) => {
+            let parts: Vec<String> = status.part.into_iter().collect();
+            let files = git::status()?;
+            let mut counts = vec![];
+
+            for (name, walker) in config_file.walkers(&parts)? {
+                let mut part_counts = git::StatusCounts::default();
+
+                for (_, file_status) in files.iter().filter(|(file, _)| walker.is_match(file)) {
+                    part_counts.add(file_status);
+                }
+                counts.push((name, part_counts));
+            }
+
+            if cli.format == Format::Text {
+                let mut part_color = ColorSpec::new();
+                part_color.set_bold(true);
+
+                for (name, counts) in counts {
+                    stdout.set_color(&part_color)?;
+                    write!(stdout, "{name}")?;
+                    stdout.reset()?;
+
+                    if counts.is_clean() {
+                        writeln!(stdout, ": clean")?;
+                    } else {
+                        writeln!(
+                            stdout,
+                            ": {} modified, {} staged, {} untracked, {} deleted",
+                            counts.modified, counts.staged, counts.untracked, counts.deleted
+                        )?;
+                    }
+                }
+            } else {
+                let mut writer = RecordWriter::new(cli.format, &mut stdout);
+
+                for (name, counts) in counts.iter() {
+                    writer.write(&[
+                        ("part", Value::Str(name)),
+                        ("modified", Value::Int(counts.modified as u64)),
+                        ("staged", Value::Int(counts.staged as u64)),
+                        ("untracked", Value::Int(counts.untracked as u64)),
+                        ("deleted", Value::Int(counts.deleted as u64)),
+                    ])?;
+                }
+                writer.finish()?;
+            }
+        }
+        #[cfg(feature = "monitor")]
+        Action::Watch(watch) => {
+            if watch.exec.is_some() {
+                text_only(&cli.format, "watch --exec")?;
+            } else if cli.format == Format::Json {
+                return Err(Error::UnsupportedFormat {
+                    command: "watch".to_string(),
+                    supported: "text, ndjson, nul or a template".to_string(),
+                }
+                .into());
+            }
+
+            let parts = config_file.walkers(&watch.parts)?;
+            let monitor = monitor::Monitor::new(parts)?;
+
+            match watch.exec {
+                Some(command) => {
+                    let mut exec = monitor::Exec::new(command);
+                    let delay = std::time::Duration::from_millis(watch.debounce);
+                    monitor.watch_debounced(delay, |events| exec.run(&events))?;
+                }
+                None if cli.format != Format::Text => {
+                    let mut writer = RecordWriter::new(cli.format, &mut stdout);
+
+                    monitor.watch(|event| {
+                        let parts: Vec<&str> = event.parts.iter().map(String::as_str).collect();
+                        writer.write(&[
+                            ("path", Value::Path(&event.path)),
+                            ("kind", Value::Str(&event.kind.to_string())),
+                            ("parts", Value::Strs(&parts)),
+                        ])?;
+                        // Events are printed as they occur
+                        writer.flush()
+                    })?;
+                }
+                None => monitor.watch(|event| {
+                    writeln!(
+                        stdout,
+                        "{} {} ({})",
+                        event.kind,
+                        event.path.display(),
+                        event.parts.join(", ")
+                    )?;
+                    Ok(())
+                })?,
+            }
+        }
+        Action::Check(check) => {
+            let diagnostics = check::check(&config_file)?;
+            let errors = diagnostics
+                .iter()
+                .filter(|diagnostic| diagnostic.severity == check::Severity::Error)
+                .count();
+            let warnings = diagnostics.len() - errors;
+
+            if cli.format == Format::Text {
+                for diagnostic in diagnostics.iter() {
+                    let mut color = ColorSpec::new();
+                    color.set_bold(true).set_fg(Some(match diagnostic.severity {
+                        check::Severity::Error => Color::Red,
+                        check::Severity::Warning => Color::Yellow,
+                    }));
+                    stdout.set_color(&color)?;
+                    write!(stdout, "{}", diagnostic.severity)?;
+                    stdout.reset()?;
+                    match diagnostic.location() {
+                        Some(location) => writeln!(stdout, ": {location}: {}", diagnostic.message)?,
+                        None => writeln!(stdout, ": {}", diagnostic.message)?,
+                    }
+                }
+                if diagnostics.is_empty() {
+                    writeln!(stdout, "No problems found")?;
+                }
+            } else {
+                let mut writer = RecordWriter::new(cli.format.clone(), &mut stdout);
+
+                for diagnostic in diagnostics.iter() {
+                    writer.write(&[
+                        ("severity", Value::Str(&diagnostic.severity.to_string())),
+                        ("part", Value::Str(diagnostic.part.as_deref().unwrap_or(""))),
+                        ("key", Value::Str(diagnostic.key.as_deref().unwrap_or(""))),
+                        ("message", Value::Str(&diagnostic.message)),
+                    ])?;
+                }
+                writer.finish()?;
+            }
+
+            if errors > 0 || (check.strict && warnings > 0) {
+                return Err(Error::CheckFailed { errors, warnings }.into());
+            }
+        }
+        Action::Explain(explain) => {
+            text_only(&cli.format, "explain")?;
+
+            let config = config_file.get_part(&explain.part)?;
+            let walker = config_file.walker(&explain.part)?;
+            let current_dir = std::env::current_dir()?;
+            let path = explain
+                .path
+                .strip_prefix(&current_dir)
+                .unwrap_or(&explain.path);
+            let hidden = walker.is_hidden(path);
+            let rules = walk::Gitignores::new().rules(path, path.is_dir());
+            let gitignore_rule = walker.gitignore_rule(path, &rules);
+            let included = !hidden && gitignore_rule.is_none() && walker.is_match(path);
+            let mut part_color = ColorSpec::new();
+            part_color.set_bold(true);
+
+            write!(stdout, "{}: ", path.display())?;
+            stdout.set_color(&part_color)?;
+            if included {
+                write!(stdout, "in part {:?}", explain.part)?;
+            } else {
+                write!(stdout, "not in part {:?}", explain.part)?;
+            }
+            stdout.reset()?;
+            writeln!(stdout)?;
+
+            for pattern in config.directory_patterns() {
+                let inside = config::expand_directory(&config.root, pattern)
+                    .iter()
+                    .any(|directory| walk::absolute(path).starts_with(directory));
+                writeln!(
+                    stdout,
+                    "  directory {pattern:?}: {}",
+                    if inside {
+                        "contains the path"
+                    } else {
+                        "does not contain the path"
+                    }
+                )?;
+            }
+
+            if !walker.ignore_hidden() {
+                writeln!(stdout, "  hidden files: not skipped")?;
+            } else if hidden {
+                writeln!(stdout, "  hidden files: skipped, the path is hidden")?;
+            } else {
+                writeln!(stdout, "  hidden files: skipped, the path is not hidden")?;
+            }
+
+            match (walker.use_gitignore(), gitignore_rule) {
+                (false, _) => writeln!(stdout, "  .gitignore: not used")?,
+                (true, Some(rule)) => writeln!(
+                    stdout,
+                    "  .gitignore: ignored by {:?} in {}",
+                    rule.pattern,
+                    rule.file
+                        .strip_prefix(&current_dir)
+                        .unwrap_or(&rule.file)
+                        .display()
+                )?,
+                (true, None) => writeln!(stdout, "  .gitignore: not ignored")?,
+            }
+
+            for (side, patterns) in [
+                ("include", config.include_patterns_matching(path)),
+                ("exclude", config.exclude_patterns_matching(path)),
+            ] {
+                if patterns.is_empty() {
+                    writeln!(stdout, "  {side}: no pattern matches")?;
+                }
+                for pattern in patterns {
+                    writeln!(
+                        stdout,
+                        "  {side}: {}[{}] = {:?} matches",
+                        pattern.key, pattern.index, pattern.text
+                    )?;
+                }
+            }
+
+            for (key, references) in [
+                ("include_parts", &config.include_parts),
+                ("exclude_parts", &config.exclude_parts),
+            ] {
+                for reference in references {
+                    let contains = config_file.matcher(reference)?.is_match(path);
+                    writeln!(
+                        stdout,
+                        "  {key}: {reference:?} {}",
+                        if contains {
+                            "contains the path"
+                        } else {
+                            "does not contain the path"
+                        }
+                    )?;
+                }
+            }
+        }
+        Action::Which(which) => {
+            let current_dir = std::env::current_dir()?;
+            let walkers = config_file
+                .part_names()
+                .into_iter()
+                .map(|name| Ok((name, config_file.walker(name)?)))
+                .collect::<Result<Vec<_>>>()?;
+            let mut writer = RecordWriter::new(cli.format.clone(), &mut stdout);
+
+            for path in which.paths.iter() {
+                let relative = path.strip_prefix(&current_dir).unwrap_or(path);
+                let names: Vec<&str> = walkers
+                    .iter()
+                    .filter(|(_, walker)| !walker.is_hidden(relative) && walker.is_match(relative))
+                    .map(|(name, _)| *name)
+                    .collect();
+
+                writer.write(&[("path", Value::Path(path)), ("parts", Value::Strs(&names))])?;
+            }
+            writer.finish()?;
         }
         #[cfg(feature = "clap_complete")]
-        Action::Complete(complete) => match complete.shell.as_str() {
-            "bash" => generate(
-                shells::Bash,
-                &mut Cli::command(),
-                env!("CARGO_BIN_NAME"),
-                &mut stdout,
-            ),
-            "elvish" => generate(
-                shells::Elvish,
-                &mut Cli::command(),
-                env!("CARGO_BIN_NAME"),
-                &mut stdout,
-            ),
-            "fish" => generate(
-                shells::Fish,
-                &mut Cli::command(),
-                env!("CARGO_BIN_NAME"),
-                &mut stdout,
-            ),
-            "powershell" => generate(
-                shells::PowerShell,
-                &mut Cli::command(),
-                env!("CARGO_BIN_NAME"),
-                &mut stdout,
-            ),
-            "zsh" => generate(
-                shells::Zsh,
-                &mut Cli::command(),
-                env!("CARGO_BIN_NAME"),
-                &mut stdout,
-            ),
-            _ => unreachable!(),
-        },
+        Action::Complete(complete) => {
+            text_only(&cli.format, "complete")?;
+
+            match complete.shell.as_str() {
+                "bash" => generate(
+                    shells::Bash,
+                    &mut Cli::command(),
+                    env!("CARGO_BIN_NAME"),
+                    &mut stdout,
+                ),
+                "elvish" => generate(
+                    shells::Elvish,
+                    &mut Cli::command(),
+                    env!("CARGO_BIN_NAME"),
+                    &mut stdout,
+                ),
+                "fish" => generate(
+                    shells::Fish,
+                    &mut Cli::command(),
+                    env!("CARGO_BIN_NAME"),
+                    &mut stdout,
+                ),
+                "powershell" => generate(
+                    shells::PowerShell,
+                    &mut Cli::command(),
+                    env!("CARGO_BIN_NAME"),
+                    &mut stdout,
+                ),
+                "zsh" => generate(
+                    shells::Zsh,
+                    &mut Cli::command(),
+                    env!("CARGO_BIN_NAME"),
+                    &mut stdout,
+                ),
+                _ => unreachable!(),
+            }
+        }
     }
 
     Ok(())
@@ -0,0 +1,379 @@
+//! Filesystem monitoring of parts.
+//!
+//! Events are filtered using the same rules as [`Walker`], so that only
+//! files belonging to (at least) one of the monitored parts are reported.
+use crate::error::Result;
+use crate::walk::{Gitignores, Matcher, Walker};
+use itertools::Itertools;
+use log::{info, warn};
+use notify::event::{ModifyKind, RenameMode};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+#[cfg(unix)]
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+/// Kind of filesystem event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventKind {
+    Created,
+    Modified,
+    Removed,
+    Renamed,
+}
+
+impl std::fmt::Display for EventKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EventKind::Created => write!(f, "created"),
+            EventKind::Modified => write!(f, "modified"),
+            EventKind::Removed => write!(f, "removed"),
+            EventKind::Renamed => write!(f, "renamed"),
+        }
+    }
+}
+
+impl EventKind {
+    fn from_notify(kind: &notify::EventKind) -> Option<Self> {
+        match kind {
+            notify::EventKind::Create(_) => Some(EventKind::Created),
+            // Renames are also reported as separate `From` and `To` events
+            notify::EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => None,
+            notify::EventKind::Modify(ModifyKind::Name(_)) => Some(EventKind::Renamed),
+            notify::EventKind::Modify(ModifyKind::Metadata(_)) => None,
+            notify::EventKind::Modify(_) => Some(EventKind::Modified),
+            notify::EventKind::Remove(_) => Some(EventKind::Removed),
+            _ => None,
+        }
+    }
+}
+
+/// A filesystem event on a file, tagged with the parts it belongs to.
+#[derive(Clone, Debug)]
+pub struct Event {
+    pub kind: EventKind,
+    /// Path of the file, relative to the current directory.
+    pub path: PathBuf,
+    /// Names of the parts that contain the file.
+    pub parts: Vec<String>,
+}
+
+/// Watch the directories of one or more parts.
+pub struct Monitor {
+    parts: Vec<(String, Walker)>,
+    directories: Vec<PathBuf>,
+    gitignores: Gitignores,
+    current_dir: PathBuf,
+}
+
+impl Monitor {
+    pub fn new(parts: Vec<(String, Walker)>) -> Result<Self> {
+        let directories: Vec<PathBuf> = Matcher::Any(
+            parts
+                .iter()
+                .map(|(_, walker)| walker.matcher().clone())
+                .collect(),
+        )
+        .directories()
+        .into_iter()
+        .map(|directory| {
+            if directory.as_os_str().is_empty() {
+                PathBuf::from(".")
+            } else {
+                directory
+            }
+        })
+        .collect();
+
+        Ok(Monitor {
+            parts,
+            directories,
+            gitignores: Gitignores::new(),
+            current_dir: std::env::current_dir()?.canonicalize()?,
+        })
+    }
+
+    /// Return the path relative to the current directory.
+    fn relative_path(&self, path: &Path) -> PathBuf {
+        path.strip_prefix(&self.current_dir)
+            .unwrap_or(path)
+            .to_path_buf()
+    }
+
+    /// Return the names of the parts that contain a given path.
+    fn matching_parts(&self, path: &Path) -> Vec<String> {
+        if path
+            .components()
+            .any(|component| component.as_os_str() == ".git")
+        {
+            return vec![];
+        }
+
+        let is_dir = path.is_dir();
+
+        if is_dir {
+            return vec![];
+        }
+
+        // Gitignore files are read once per path, and only if needed
+        let rules = if self.parts.iter().any(|(_, walker)| walker.use_gitignore()) {
+            self.gitignores.rules(path, is_dir)
+        } else {
+            vec![]
+        };
+
+        self.parts
+            .iter()
+            .filter(|(_, walker)| {
+                !walker.is_hidden(path)
+                    && walker.gitignore_rule(path, &rules).is_none()
+                    && walker.is_match(path)
+            })
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Start watching the directories, returning the watcher, which must
+    /// be kept alive, and the channel on which raw events are received.
+    fn start(&self) -> Result<(RecommendedWatcher, Receiver<notify::Result<notify::Event>>)> {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut watcher = notify::recommended_watcher(tx)?;
+
+        // Directories that do not exist (yet) cannot be watched
+        for directory in self
+            .directories
+            .iter()
+            .filter(|directory| directory.is_dir())
+        {
+            watcher.watch(directory, RecursiveMode::Recursive)?;
+        }
+
+        Ok((watcher, rx))
+    }
+
+    /// Convert a raw event into events on files that belong to some part.
+    fn convert(&self, event: notify::Event) -> Vec<Event> {
+        let kind = match EventKind::from_notify(&event.kind) {
+            Some(kind) => kind,
+            None => return vec![],
+        };
+
+        event
+            .paths
+            .iter()
+            .filter_map(|path| {
+                let path = self.relative_path(path);
+                let parts = self.matching_parts(&path);
+
+                if parts.is_empty() {
+                    None
+                } else {
+                    Some(Event { kind, path, parts })
+                }
+            })
+            .collect()
+    }
+
+    /// Watch for changes, calling `callback` for every event that
+    /// concerns at least one part.
+    ///
+    /// This function blocks until an error occurs, either from the watcher
+    /// or returned by the callback.
+    pub fn watch<F: FnMut(Event) -> Result<()>>(&self, mut callback: F) -> Result<()> {
+        let (_watcher, rx) = self.start()?;
+
+        for result in rx {
+            for event in self.convert(result?) {
+                callback(event)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Watch for changes, calling `callback` once per burst of events.
+    ///
+    /// A burst ends when no event was received during `delay`, so that,
+    /// e.g., a `git checkout` touching many files triggers a single call.
+    pub fn watch_debounced<F: FnMut(Vec<Event>) -> Result<()>>(
+        &self,
+        delay: Duration,
+        mut callback: F,
+    ) -> Result<()> {
+        let (_watcher, rx) = self.start()?;
+
+        while let Ok(result) = rx.recv() {
+            let mut events = self.convert(result?);
+
+            loop {
+                match rx.recv_timeout(delay) {
+                    Ok(result) => events.extend(self.convert(result?)),
+                    Err(RecvTimeoutError::Timeout) => break,
+                    Err(RecvTimeoutError::Disconnected) => return Ok(()),
+                }
+            }
+
+            if !events.is_empty() {
+                callback(events)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Environment variable containing the changed files, one per line.
+pub const CHANGED_FILES_VAR: &str = "PARTS_CHANGED_FILES";
+/// Environment variable containing the changed parts, one per line.
+pub const CHANGED_PARTS_VAR: &str = "PARTS_CHANGED_PARTS";
+
+/// Process group of the running command, if any.
+///
+/// As the command runs in its own process group, it does not receive the
+/// signals sent by the terminal (e.g., on Ctrl-C), so it is killed when
+/// `parts` receives them.
+#[cfg(unix)]
+static RUNNING_GROUP: AtomicI32 = AtomicI32::new(0);
+
+#[cfg(unix)]
+extern "C" fn kill_running_group(signal: libc::c_int) {
+    let group = RUNNING_GROUP.load(Ordering::SeqCst);
+
+    // Only async-signal-safe functions are called
+    unsafe {
+        if group > 0 {
+            libc::kill(-group, libc::SIGKILL);
+        }
+        libc::signal(signal, libc::SIG_DFL);
+        libc::raise(signal);
+    }
+}
+
+/// Run a shell command each time it is given a burst of events.
+///
+/// If the previous run is still in progress, it is killed, along with the
+/// processes it started, before the command is started again.
+pub struct Exec {
+    command: String,
+    child: Option<Child>,
+}
+
+impl Exec {
+    pub fn new(command: String) -> Self {
+        #[cfg(unix)]
+        for signal in [libc::SIGHUP, libc::SIGINT, libc::SIGTERM] {
+            unsafe {
+                libc::signal(
+                    signal,
+                    kill_running_group as *const () as libc::sighandler_t,
+                );
+            }
+        }
+
+        Exec {
+            command,
+            child: None,
+        }
+    }
+
+    /// Return the shell running the command, in its own process group, so
+    /// that the processes it starts can be killed along with it.
+    #[cfg(unix)]
+    fn shell(&self) -> Command {
+        use std::os::unix::process::CommandExt;
+
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(&self.command).process_group(0);
+        command
+    }
+
+    #[cfg(not(unix))]
+    fn shell(&self) -> Command {
+        let mut command = Command::new("cmd");
+        command.arg("/C").arg(&self.command);
+        command
+    }
+
+    /// Kill a running command and the processes it started.
+    #[cfg(unix)]
+    fn kill(child: &mut Child) -> std::io::Result<()> {
+        // The group of the command has the same ID as the command itself
+        if unsafe { libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL) } == 0 {
+            return Ok(());
+        }
+
+        match std::io::Error::last_os_error() {
+            // The processes already exited
+            err if err.raw_os_error() == Some(libc::ESRCH) => Ok(()),
+            err => Err(err),
+        }
+    }
+
+    /// Kill a running command and the processes it started.
+    #[cfg(not(unix))]
+    fn kill(child: &mut Child) -> std::io::Result<()> {
+        let status = Command::new("taskkill")
+            .args(["/T", "/F", "/PID", &child.id().to_string()])
+            .status()?;
+
+        if status.success() {
+            Ok(())
+        } else {
+            child.kill()
+        }
+    }
+
+    /// Kill the running command, if any, and wait for it to exit.
+    pub fn cancel(&mut self) -> Result<()> {
+        if let Some(mut child) = self.child.take() {
+            if child.try_wait()?.is_none() {
+                info!("killing previous run of {:?}", self.command);
+                Self::kill(&mut child)?;
+            }
+            #[cfg(unix)]
+            RUNNING_GROUP.store(0, Ordering::SeqCst);
+            child.wait()?;
+        }
+        Ok(())
+    }
+
+    /// (Re)start the command, passing the changed files and parts through
+    /// the [`CHANGED_FILES_VAR`] and [`CHANGED_PARTS_VAR`] environment variables.
+    pub fn run(&mut self, events: &[Event]) -> Result<()> {
+        self.cancel()?;
+
+        let files: Vec<String> = events
+            .iter()
+            .map(|event| event.path.to_string_lossy().into_owned())
+            .sorted()
+            .dedup()
+            .collect();
+        let parts: Vec<&str> = events
+            .iter()
+            .flat_map(|event| event.parts.iter().map(String::as_str))
+            .sorted()
+            .dedup()
+            .collect();
+
+        info!("running {:?} for parts: {}", self.command, parts.join(", "));
+        let child = self
+            .shell()
+            .env(CHANGED_FILES_VAR, files.join("\n"))
+            .env(CHANGED_PARTS_VAR, parts.join("\n"))
+            .spawn()?;
+        #[cfg(unix)]
+        RUNNING_GROUP.store(child.id() as i32, Ordering::SeqCst);
+        self.child = Some(child);
+        Ok(())
+    }
+}
+
+impl Drop for Exec {
+    fn drop(&mut self) {
+        if let Err(err) = self.cancel() {
+            warn!("{err}");
+        }
+    }
+}
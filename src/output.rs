@@ -0,0 +1,381 @@
+//! Machine-readable output formats.
+//!
+//! Commands that support machine-readable output describe each item they
+//! print as a [`Record`], i.e., a list of named fields, which is then
+//! written by a [`RecordWriter`] in the requested [`Format`].
+use crate::error::{Error, Result};
+use serde_json::{Map, Value as Json};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Output format, as selected with `--format`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Human-readable text, the default.
+    Text,
+    /// A single JSON array of objects.
+    Json,
+    /// One JSON object per line.
+    Ndjson,
+    /// The first field of each record, terminated by a NUL byte.
+    Nul,
+    /// A user-defined template, e.g., `"{path}\t{size}"`.
+    Template(Template),
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "ndjson" => Ok(Format::Ndjson),
+            "nul" => Ok(Format::Nul),
+            s if s.contains('{') => Ok(Format::Template(s.parse()?)),
+            s => Err(Error::InvalidFormat {
+                format: s.to_string(),
+                reason: "expected one of text, json, ndjson, nul, or a template containing \
+                         fields such as {path}"
+                    .to_string(),
+            }),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Piece {
+    Literal(Vec<u8>),
+    Field(String),
+}
+
+/// Template in which `{field}` placeholders are replaced by record fields.
+///
+/// Braces are escaped by doubling them, and the `\t`, `\n`, `\0` and `\\`
+/// escape sequences are supported. Each record is followed by a newline,
+/// unless the template already ends with `\n` or `\0`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Template {
+    pieces: Vec<Piece>,
+}
+
+impl FromStr for Template {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = |reason: &str| Error::InvalidFormat {
+            format: s.to_string(),
+            reason: reason.to_string(),
+        };
+        let mut pieces = vec![];
+        let mut literal = vec![];
+        let mut chars = s.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    literal.push(b'{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    literal.push(b'}');
+                }
+                '{' => {
+                    let (field, rest) = chars
+                        .as_str()
+                        .split_once('}')
+                        .ok_or_else(|| invalid("unclosed '{'"))?;
+                    if !literal.is_empty() {
+                        pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+                    }
+                    pieces.push(Piece::Field(field.trim().to_string()));
+                    chars = rest.chars();
+                }
+                '}' => return Err(invalid("unmatched '}', use '}}' to escape it")),
+                '\\' => match chars.next() {
+                    Some('t') => literal.push(b'\t'),
+                    Some('n') => literal.push(b'\n'),
+                    Some('0') => literal.push(b'\0'),
+                    Some('\\') => literal.push(b'\\'),
+                    _ => return Err(invalid("unknown escape sequence")),
+                },
+                c => literal.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+            }
+        }
+
+        if !literal.is_empty() {
+            pieces.push(Piece::Literal(literal));
+        }
+
+        match pieces.last_mut() {
+            Some(Piece::Literal(bytes)) if matches!(bytes.last(), Some(b'\n' | b'\0')) => {}
+            Some(Piece::Literal(bytes)) => bytes.push(b'\n'),
+            _ => pieces.push(Piece::Literal(b"\n".to_vec())),
+        }
+
+        Ok(Template { pieces })
+    }
+}
+
+/// Value of a record field.
+#[derive(Clone, Debug)]
+pub enum Value<'a> {
+    Str(&'a str),
+    Path(&'a Path),
+    Int(u64),
+    Bool(bool),
+    Paths(&'a [PathBuf]),
+    Strs(&'a [&'a str]),
+}
+
+/// Named fields describing an item of output.
+pub type Record<'a> = [(&'static str, Value<'a>)];
+
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> std::borrow::Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().into()
+}
+
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> std::borrow::Cow<'_, [u8]> {
+    path.to_string_lossy().into_owned().into_bytes().into()
+}
+
+impl Value<'_> {
+    /// Raw bytes of the value, paths being written as-is, even if they
+    /// are not valid UTF-8.
+    fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Value::Str(s) => s.as_bytes().to_vec(),
+            Value::Path(path) => path_bytes(path).into_owned(),
+            Value::Int(n) => n.to_string().into_bytes(),
+            Value::Bool(b) => b.to_string().into_bytes(),
+            Value::Paths(paths) => paths
+                .iter()
+                .map(|path| path_bytes(path))
+                .collect::<Vec<_>>()
+                .join(&b','),
+            Value::Strs(strs) => strs.join(",").into_bytes(),
+        }
+    }
+}
+
+/// Convert a record into a JSON object.
+///
+/// Paths that are not valid UTF-8 are converted lossily, and their raw
+/// bytes are added in an extra `<field>_bytes` field (except in lists).
+fn to_json(record: &Record) -> Json {
+    let mut object = Map::new();
+
+    for (name, value) in record.iter() {
+        let json = match value {
+            Value::Str(s) => Json::from(*s),
+            Value::Path(path) => {
+                if path.to_str().is_none() {
+                    object.insert(
+                        format!("{name}_bytes"),
+                        Json::from(path_bytes(path).to_vec()),
+                    );
+                }
+                Json::from(path.to_string_lossy())
+            }
+            Value::Int(n) => Json::from(*n),
+            Value::Bool(b) => Json::from(*b),
+            Value::Paths(paths) => paths
+                .iter()
+                .map(|path| Json::from(path.to_string_lossy()))
+                .collect(),
+            Value::Strs(strs) => Json::from(strs.to_vec()),
+        };
+        object.insert(name.to_string(), json);
+    }
+
+    Json::Object(object)
+}
+
+/// Write records in a given format.
+///
+/// [`RecordWriter::finish`] must be called once all records are written.
+pub struct RecordWriter<W: Write> {
+    format: Format,
+    writer: W,
+    count: usize,
+}
+
+impl<W: Write> RecordWriter<W> {
+    pub fn new(format: Format, writer: W) -> Self {
+        RecordWriter {
+            format,
+            writer,
+            count: 0,
+        }
+    }
+
+    pub fn write(&mut self, record: &Record) -> Result<()> {
+        match &self.format {
+            Format::Text => {
+                let values: Vec<Vec<u8>> =
+                    record.iter().map(|(_, value)| value.to_bytes()).collect();
+                self.writer.write_all(&values.join(&b'\t'))?;
+                self.writer.write_all(b"\n")?;
+            }
+            Format::Json => {
+                let separator: &[u8] = if self.count == 0 { b"[\n  " } else { b",\n  " };
+                self.writer.write_all(separator)?;
+                serde_json::to_writer(&mut self.writer, &to_json(record))?;
+            }
+            Format::Ndjson => {
+                serde_json::to_writer(&mut self.writer, &to_json(record))?;
+                self.writer.write_all(b"\n")?;
+            }
+            Format::Nul => {
+                if let Some((_, value)) = record.first() {
+                    self.writer.write_all(&value.to_bytes())?;
+                }
+                self.writer.write_all(b"\0")?;
+            }
+            Format::Template(template) => {
+                for piece in template.pieces.iter() {
+                    match piece {
+                        Piece::Literal(bytes) => self.writer.write_all(bytes)?,
+                        Piece::Field(field) => {
+                            let (_, value) = record
+                                .iter()
+                                .find(|(name, _)| name == field)
+                                .ok_or_else(|| Error::UnknownTemplateField {
+                                    field: field.clone(),
+                                    available: record
+                                        .iter()
+                                        .map(|(name, _)| *name)
+                                        .collect::<Vec<_>>()
+                                        .join(", "),
+                                })?;
+                            self.writer.write_all(&value.to_bytes())?;
+                        }
+                    }
+                }
+            }
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Flush the records written so far, e.g., when they are written as
+    /// events occur.
+    pub fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Terminate the output, and return the underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        if self.format == Format::Json {
+            let end: &[u8] = if self.count == 0 { b"[]\n" } else { b"\n]\n" };
+            self.writer.write_all(end)?;
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write records in a format, and return the output.
+    fn write(format: &str, records: &[&Record]) -> Result<String> {
+        let mut writer = RecordWriter::new(format.parse()?, Vec::new());
+
+        for record in records {
+            writer.write(record)?;
+        }
+        Ok(String::from_utf8(writer.finish()?).unwrap())
+    }
+
+    #[test]
+    fn templates() {
+        let record: &Record = &[("path", Value::Str("a.rs")), ("size", Value::Int(3))];
+
+        assert_eq!(write("{path}", &[record]).unwrap(), "a.rs\n");
+        assert_eq!(write("{ path }:{size}", &[record]).unwrap(), "a.rs:3\n");
+        assert_eq!(write("{{{path}}}", &[record]).unwrap(), "{a.rs}\n");
+        assert_eq!(
+            write("{path}\\t{size}\\\\", &[record]).unwrap(),
+            "a.rs\t3\\\n"
+        );
+        // No newline is added after a trailing newline or NUL byte
+        assert_eq!(
+            write("{path}\\n", &[record, record]).unwrap(),
+            "a.rs\na.rs\n"
+        );
+        assert_eq!(
+            write("{path}\\0", &[record, record]).unwrap(),
+            "a.rs\0a.rs\0"
+        );
+    }
+
+    #[test]
+    fn template_errors() {
+        for template in ["{path", "path}", "{path}\\x", "{path}}"] {
+            assert!(
+                matches!(template.parse::<Format>(), Err(Error::InvalidFormat { .. })),
+                "{template:?} should be invalid"
+            );
+        }
+
+        let record: &Record = &[("path", Value::Str("a.rs")), ("size", Value::Int(3))];
+        match write("{name}", &[record]) {
+            Err(Error::UnknownTemplateField { field, available }) => {
+                assert_eq!(field, "name");
+                assert_eq!(available, "path, size");
+            }
+            result => panic!("expected an unknown field, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn formats() {
+        let a: &Record = &[
+            ("path", Value::Str("a.rs")),
+            ("parts", Value::Strs(&["x", "y"])),
+        ];
+        let b: &Record = &[("path", Value::Str("b.rs")), ("parts", Value::Strs(&[]))];
+
+        assert_eq!(write("text", &[a, b]).unwrap(), "a.rs\tx,y\nb.rs\t\n");
+        assert_eq!(write("nul", &[a, b]).unwrap(), "a.rs\0b.rs\0");
+        assert_eq!(
+            write("ndjson", &[a, b]).unwrap(),
+            "{\"path\":\"a.rs\",\"parts\":[\"x\",\"y\"]}\n{\"path\":\"b.rs\",\"parts\":[]}\n"
+        );
+        assert_eq!(
+            write("json", &[a, b]).unwrap(),
+            "[\n  {\"path\":\"a.rs\",\"parts\":[\"x\",\"y\"]},\n  \
+             {\"path\":\"b.rs\",\"parts\":[]}\n]\n"
+        );
+        assert_eq!(write("json", &[]).unwrap(), "[]\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_paths() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = Path::new(std::ffi::OsStr::from_bytes(b"a\xff.rs"));
+        let record: &Record = &[("path", Value::Path(path))];
+        let mut writer = RecordWriter::new(Format::Ndjson, Vec::new());
+        writer.write(record).unwrap();
+
+        assert_eq!(
+            String::from_utf8(writer.finish().unwrap()).unwrap(),
+            "{\"path_bytes\":[97,255,46,114,115],\"path\":\"a\u{fffd}.rs\"}\n"
+        );
+
+        // Paths are written as-is in other formats
+        let mut writer = RecordWriter::new(Format::Nul, Vec::new());
+        writer.write(record).unwrap();
+        assert_eq!(writer.finish().unwrap(), b"a\xff.rs\0");
+    }
+}
@@ -0,0 +1,178 @@
+//! Snapshots of parts, used to monitor changes without relying on a VCS.
+//!
+//! A snapshot is a manifest of the files in a part, with their sizes,
+//! modification times and content hashes, stored as a TOML file.
+use crate::error::Result;
+use crate::expr::Expr;
+use crate::hash::{hash_file, to_hex};
+use crate::walk::{absolute, relative_to};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Manifest entry of a single file.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileEntry {
+    /// Size, in bytes.
+    pub size: u64,
+    /// Modification time, in nanoseconds since the Unix epoch.
+    pub mtime: u64,
+    /// SHA-256 hash of the contents, in hexadecimal.
+    pub hash: String,
+}
+
+impl FileEntry {
+    /// Return the size and modification time of a file.
+    fn stat(path: &Path) -> Result<(u64, u64)> {
+        let metadata = std::fs::metadata(path)?;
+        let mtime = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_nanos() as u64)
+            .unwrap_or_default();
+        Ok((metadata.len(), mtime))
+    }
+
+    pub fn new(path: &Path) -> Result<Self> {
+        let (size, mtime) = Self::stat(path)?;
+        let hash = to_hex(&hash_file(path)?);
+        Ok(FileEntry { size, mtime, hash })
+    }
+
+    /// Return whether a file differs from this entry.
+    ///
+    /// Contents are only hashed if the size is the same but the modification
+    /// time differs, so that unchanged files are cheap to check.
+    pub fn is_modified(&self, path: &Path) -> Result<bool> {
+        let (size, mtime) = Self::stat(path)?;
+
+        if size != self.size {
+            Ok(true)
+        } else if mtime == self.mtime {
+            Ok(false)
+        } else {
+            Ok(to_hex(&hash_file(path)?) != self.hash)
+        }
+    }
+}
+
+/// Return the name of the file in which the snapshot of a part, or of a
+/// set expression of parts, is stored.
+///
+/// Expressions are written in canonical form (see [`Expr`]), so that, e.g.,
+/// `src|tests` and `src | tests` share a snapshot. Characters other than
+/// ASCII letters, digits, `-`, `_` and `.` are percent-encoded, so that the
+/// name is valid on every platform.
+///
+/// # Examples
+///
+/// ```
+/// # use parts::snapshot::file_name;
+/// assert_eq!(file_name(&"src".parse().unwrap()), "src.toml");
+/// assert_eq!(file_name(&"src|tests".parse().unwrap()), "src%20%7C%20tests.toml");
+/// ```
+pub fn file_name(expr: &Expr) -> String {
+    let mut name = String::new();
+
+    for byte in expr.to_string().bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.') {
+            name.push(byte as char);
+        } else {
+            name.push_str(&format!("%{byte:02X}"));
+        }
+    }
+
+    name + ".toml"
+}
+
+/// Return the key of a file in a manifest, i.e., its path relative to
+/// the (absolute) root.
+fn key(path: &Path, root: &Path) -> String {
+    relative_to(&absolute(path), root)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Manifest of all files in a part, indexed by path relative to the
+/// directory of the config file.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub files: BTreeMap<String, FileEntry>,
+}
+
+/// Differences between a snapshot and the current files, whose paths are
+/// relative to the current directory.
+#[derive(Clone, Debug, Default)]
+pub struct SnapshotDiff {
+    pub added: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+    pub modified: Vec<PathBuf>,
+}
+
+impl SnapshotDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+impl Snapshot {
+    /// Take a snapshot of the given files, indexed by their path relative
+    /// to `root` (usually the directory of the config file).
+    ///
+    /// Paths are relative to the current directory, and are usually obtained
+    /// from [`Walker::collect`](crate::walk::Walker::collect).
+    pub fn new(paths: &[PathBuf], root: &Path) -> Result<Self> {
+        let root = absolute(root);
+        let files = paths
+            .iter()
+            .map(|path| Ok((key(path, &root), FileEntry::new(path)?)))
+            .collect::<Result<_>>()?;
+        Ok(Snapshot { files })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(toml::from_str(&content)?)
+    }
+
+    /// Save the snapshot, creating parent directories if needed.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Compare the snapshot against the given files, see [`Snapshot::new`].
+    pub fn diff(&self, paths: &[PathBuf], root: &Path) -> Result<SnapshotDiff> {
+        let root = absolute(root);
+        let current_dir = absolute(Path::new(""));
+        let mut diff = SnapshotDiff::default();
+        let mut seen = std::collections::HashSet::new();
+
+        for path in paths.iter() {
+            let key = key(path, &root);
+
+            match self.files.get(&key) {
+                Some(entry) => {
+                    if entry.is_modified(path)? {
+                        diff.modified.push(path.clone());
+                    }
+                    seen.insert(key);
+                }
+                None => diff.added.push(path.clone()),
+            }
+        }
+
+        diff.removed = self
+            .files
+            .keys()
+            .filter(|key| !seen.contains(*key))
+            .map(|key| relative_to(&root.join(key), &current_dir))
+            .collect();
+
+        Ok(diff)
+    }
+}
@@ -1,6 +1,14 @@
-use crate::config::{merge_globs_and_regexes, Config};
+use crate::config::{directory_glob, is_glob, merge_globs_and_regexes, Config};
+use crossbeam_channel::Sender;
+use globset::GlobMatcher;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use itertools::Itertools;
+use log::warn;
+use regex::bytes::RegexSet;
+use std::collections::BTreeMap;
+use std::fs::Metadata;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
 use termcolor::BufferWriter;
 
 #[cfg(unix)]
@@ -16,77 +24,725 @@ fn write_path<W: Write>(mut wtr: W, path: &Path) -> std::io::Result<()> {
     wtr.write_all(b"\n")
 }
 
+/// Remove all `.` components from a path, so that `./src/main.rs`
+/// and `src/main.rs` compare equal.
+pub fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| !matches!(component, Component::CurDir))
+        .collect()
+}
+
+/// Return the current directory, or the empty path if it is unavailable.
+///
+/// It is read on every call, so that changing the current directory
+/// (e.g., with [`std::env::set_current_dir`]) is taken into account.
+fn current_dir() -> PathBuf {
+    std::env::current_dir().unwrap_or_default()
+}
+
+/// Return the absolute version of a path, relative to the current
+/// directory, with `.` and `..` components resolved lexically.
+pub fn absolute(path: &Path) -> PathBuf {
+    absolute_from(&current_dir(), path)
+}
+
+/// Return the absolute version of a path, relative to an absolute base
+/// directory, with `.` and `..` components resolved lexically.
+///
+/// This avoids reading the current directory for every path when many
+/// paths are made absolute.
+pub fn absolute_from(base: &Path, path: &Path) -> PathBuf {
+    let mut absolute = base.to_path_buf();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                absolute.pop();
+            }
+            component => absolute.push(component),
+        }
+    }
+    absolute
+}
+
+/// Return an absolute path relative to an absolute base directory, with
+/// leading `..` components if it is outside of it.
+///
+/// # Examples
+///
+/// ```
+/// # use parts::walk::relative_to;
+/// # use std::path::Path;
+/// let base = Path::new("/project/src");
+/// assert_eq!(relative_to(Path::new("/project/src/main.rs"), base), Path::new("main.rs"));
+/// assert_eq!(relative_to(Path::new("/project/docs"), base), Path::new("../docs"));
+/// assert_eq!(relative_to(base, base), Path::new(""));
+/// ```
+pub fn relative_to(path: &Path, base: &Path) -> PathBuf {
+    let common = path
+        .components()
+        .zip(base.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    std::iter::repeat_n(Component::ParentDir, base.components().count() - common)
+        .chain(path.components().skip(common))
+        .collect()
+}
+
+/// Return whether any component of a path is hidden, i.e., starts with a dot.
+pub fn is_hidden(path: &Path) -> bool {
+    path.components().any(|component| match component {
+        Component::Normal(name) => name.to_string_lossy().starts_with('.'),
+        _ => false,
+    })
+}
+
+/// Rule of a gitignore file that ignores a path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GitignoreRule {
+    /// Absolute path of the file containing the rule.
+    pub file: PathBuf,
+    /// Pattern of the rule, as written in the file.
+    pub pattern: String,
+    /// Absolute path matched by the rule, i.e., the ignored path itself or
+    /// one of its parent directories.
+    pub path: PathBuf,
+}
+
+/// Gitignore rules, as applied during the traversal of a [`Walker`].
+///
+/// The global excludes file, `.git/info/exclude` and the `.gitignore` files
+/// from the root of the repository down to the directory containing a path
+/// apply, in increasing order of precedence. Paths outside of a git
+/// repository are never ignored.
+pub struct Gitignores {
+    global: Gitignore,
+}
+
+impl Default for Gitignores {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Gitignores {
+    /// Read the global excludes file, the other files being read for each
+    /// path.
+    pub fn new() -> Self {
+        Gitignores {
+            global: Gitignore::global().0,
+        }
+    }
+
+    /// Return the rules that ignore a path, relative to the current
+    /// directory, or its parent directories, from the deepest path.
+    ///
+    /// Gitignore files are read on every call, as they may change.
+    pub fn rules(&self, path: &Path, is_dir: bool) -> Vec<GitignoreRule> {
+        let path = absolute(path);
+        let directories: Vec<&Path> = path.ancestors().skip(1).collect();
+        let root = match directories
+            .iter()
+            .position(|directory| directory.join(".git").exists())
+        {
+            Some(root) => root,
+            None => return vec![],
+        };
+        let repository = directories[root];
+
+        let mut exclude = GitignoreBuilder::new(repository);
+        exclude.add(repository.join(".git/info/exclude"));
+
+        // From the highest to the lowest precedence, the global excludes apart
+        let gitignores: Vec<Gitignore> = directories[..=root]
+            .iter()
+            .map(|directory| Gitignore::new(directory.join(".gitignore")).0)
+            .chain(std::iter::once(
+                exclude.build().unwrap_or_else(|_| Gitignore::empty()),
+            ))
+            .collect();
+
+        let candidates = std::iter::once((path.as_path(), is_dir)).chain(
+            directories[..root]
+                .iter()
+                .map(|directory| (*directory, true)),
+        );
+        let mut rules = vec![];
+
+        for (candidate, is_dir) in candidates {
+            // A `.gitignore` file only applies below its own directory
+            let matched = gitignores
+                .iter()
+                .filter(|gitignore| {
+                    candidate.starts_with(gitignore.path()) && candidate != gitignore.path()
+                })
+                .map(|gitignore| gitignore.matched(candidate, is_dir))
+                .find(|matched| !matched.is_none())
+                .unwrap_or_else(|| {
+                    self.global
+                        .matched(relative_to(candidate, repository), is_dir)
+                });
+
+            if let ignore::Match::Ignore(glob) = matched {
+                rules.push(GitignoreRule {
+                    file: glob.from().map(Path::to_path_buf).unwrap_or_default(),
+                    pattern: glob.original().to_string(),
+                    path: candidate.to_path_buf(),
+                });
+            }
+        }
+
+        rules
+    }
+}
+
+/// Return a builder for the traversal of directories, which skips hidden
+/// and ignored files as a [`Walker`] does.
+///
+/// # Panics
+///
+/// Panics if `directories` is empty.
+pub fn walk_builder(
+    directories: &[PathBuf],
+    ignore_hidden: bool,
+    use_gitignore: bool,
+) -> ignore::WalkBuilder {
+    let mut builder = ignore::WalkBuilder::new(&directories[0]);
+
+    for directory in directories[1..].iter() {
+        builder.add(directory);
+    }
+
+    // Git internals never belong to a part, even if hidden files are not skipped
+    builder
+        .hidden(ignore_hidden)
+        .git_ignore(use_gitignore)
+        .filter_entry(|de| de.file_name() != ".git");
+    builder
+}
+
+/// A file found by a [`Walker`].
+#[derive(Clone, Debug)]
+pub struct Entry {
+    /// Path of the file, relative to the current directory.
+    pub path: PathBuf,
+    /// Depth of the file, relative to the directory it was found in.
+    pub depth: usize,
+    /// Metadata of the file, following symbolic links.
+    pub metadata: Metadata,
+}
+
+/// Directory of a part, as matched by a [`Matcher`].
+///
+/// Directory patterns with globs (e.g., `crates/*/src`) are matched
+/// lexically against the paths, so that the filesystem is not accessed.
+#[derive(Clone, Debug)]
+pub struct DirectoryMatcher {
+    /// Absolute path of the longest literal prefix of the pattern, e.g.,
+    /// `<root>/crates` for `crates/*/src`, which contains all the matching
+    /// directories.
+    pub prefix: PathBuf,
+    /// Glob of the matching directories, relative to the root of the part,
+    /// unless the pattern is literal.
+    pub glob: Option<GlobMatcher>,
+}
+
+impl DirectoryMatcher {
+    /// Return the matcher of a directory pattern, relative to `root`.
+    pub fn new(root: &Path, pattern: &str) -> Result<Self, globset::Error> {
+        let root = absolute(root);
+        // Absolute patterns (e.g., from `${config_dir}`) are made relative to
+        // the root too, as paths are matched relative to it
+        let path = relative_to(&absolute(&root.join(normalize(Path::new(pattern)))), &root);
+        let prefix: PathBuf = path
+            .components()
+            .take_while(|component| !is_glob(*component))
+            .collect();
+        let glob = if prefix == path {
+            None
+        } else {
+            Some(directory_glob(&path.to_string_lossy())?)
+        };
+
+        Ok(DirectoryMatcher {
+            prefix: absolute(&root.join(prefix)),
+            glob,
+        })
+    }
+
+    /// Return the matcher of a single directory, relative to the current
+    /// directory.
+    pub fn literal(directory: &Path) -> Self {
+        DirectoryMatcher {
+            prefix: absolute(directory),
+            glob: None,
+        }
+    }
+
+    /// Return whether an absolute path is inside a matching directory,
+    /// `root` being the (absolute) root of the part.
+    pub fn contains(&self, root: &Path, path: &Path) -> bool {
+        if !path.starts_with(&self.prefix) {
+            return false;
+        }
+
+        match &self.glob {
+            Some(glob) => relative_to(path, root)
+                .ancestors()
+                .skip(1)
+                .any(|ancestor| glob.is_match(ancestor)),
+            None => true,
+        }
+    }
+}
+
+/// Rules deciding whether a path belongs to a part.
+///
+/// Parts defined only with patterns are a single [`Matcher::Rules`], while
+/// parts composed from other parts combine several matchers.
+#[derive(Clone, Debug)]
+pub enum Matcher {
+    /// The path must be inside any of `directories`, and its path relative
+    /// to `root` must match `include` and not `exclude`.
+    ///
+    /// `root` is absolute.
+    Rules {
+        root: PathBuf,
+        directories: Vec<DirectoryMatcher>,
+        include: RegexSet,
+        exclude: RegexSet,
+    },
+    /// The path must match any of the matchers.
+    Any(Vec<Matcher>),
+    /// The path must match all of the matchers.
+    All(Vec<Matcher>),
+    /// The path must not match the matcher.
+    Not(Box<Matcher>),
+}
+
+impl Matcher {
+    /// Return a matcher that matches every path inside the current directory.
+    pub fn everything() -> Self {
+        Matcher::directory(PathBuf::new())
+    }
+
+    /// Return a matcher that matches every path inside a directory,
+    /// relative to the current directory.
+    pub fn directory(directory: PathBuf) -> Self {
+        Matcher::Rules {
+            root: absolute(&directory),
+            directories: vec![DirectoryMatcher::literal(&directory)],
+            include: RegexSet::new([""]).expect("This cannot fail"),
+            exclude: RegexSet::empty(),
+        }
+    }
+
+    /// Return whether a path, relative to the current directory, matches.
+    ///
+    /// The filesystem is not accessed, so the path does not need to exist.
+    pub fn is_match(&self, path: &Path) -> bool {
+        self.is_match_absolute(&absolute(path))
+    }
+
+    /// Return whether an absolute path matches, see [`Matcher::is_match`].
+    ///
+    /// The current directory is not read, so that it can be read once when
+    /// matching many paths.
+    pub fn is_match_absolute(&self, path: &Path) -> bool {
+        match self {
+            Matcher::Rules {
+                root,
+                directories,
+                include,
+                exclude,
+            } => {
+                if !directories
+                    .iter()
+                    .any(|directory| directory.contains(root, path))
+                {
+                    return false;
+                }
+
+                let path = relative_to(path, root);
+                let strl = path.to_string_lossy();
+                let utf8 = strl.as_bytes();
+                include.is_match(utf8) && !exclude.is_match(utf8)
+            }
+            Matcher::Any(matchers) => matchers
+                .iter()
+                .any(|matcher| matcher.is_match_absolute(path)),
+            Matcher::All(matchers) => matchers
+                .iter()
+                .all(|matcher| matcher.is_match_absolute(path)),
+            Matcher::Not(matcher) => !matcher.is_match_absolute(path),
+        }
+    }
+
+    /// Return the directories that contain all the matching paths, i.e.,
+    /// the literal prefixes of the directory patterns.
+    ///
+    /// Directories are relative to the current directory, which is the
+    /// empty path.
+    pub fn directories(&self) -> Vec<PathBuf> {
+        self.directories_from(&current_dir())
+    }
+
+    /// Return the directories, relative to an absolute `current_dir`, see
+    /// [`Matcher::directories`].
+    fn directories_from(&self, current_dir: &Path) -> Vec<PathBuf> {
+        let mut directories = match self {
+            Matcher::Rules { directories, .. } => directories
+                .iter()
+                .map(|directory| relative_to(&directory.prefix, current_dir))
+                .collect(),
+            Matcher::Any(matchers) => matchers
+                .iter()
+                .flat_map(|matcher| matcher.directories_from(current_dir))
+                .collect(),
+            // Matching paths are a subset of those of any positive matcher
+            Matcher::All(matchers) => matchers
+                .iter()
+                .find(|matcher| !matches!(matcher, Matcher::Not(_)))
+                .map(|matcher| matcher.directories_from(current_dir))
+                .unwrap_or_else(|| vec![PathBuf::new()]),
+            Matcher::Not(_) => vec![PathBuf::new()],
+        };
+        directories.sort();
+        directories.dedup();
+
+        // Nested directories are already contained in their parent
+        directories
+            .iter()
+            .filter(|directory| {
+                !directories
+                    .iter()
+                    .any(|other| other != *directory && directory.starts_with(other))
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+impl From<Config> for Matcher {
+    /// Build a matcher from the patterns of a part.
+    ///
+    /// References to other parts are ignored, as they can only be resolved
+    /// with [`ConfigFile::matcher`](crate::config::ConfigFile::matcher).
+    fn from(config: Config) -> Self {
+        let directories = config
+            .directory_patterns()
+            .into_iter()
+            .filter_map(
+                |pattern| match DirectoryMatcher::new(&config.root, pattern) {
+                    Ok(directory) => Some(directory),
+                    Err(err) => {
+                        warn!("{err}");
+                        None
+                    }
+                },
+            )
+            .collect();
+
+        Matcher::Rules {
+            root: absolute(&config.root),
+            directories,
+            include: merge_globs_and_regexes(config.globs, config.regexes),
+            exclude: merge_globs_and_regexes(config.exclude_globs, config.exclude_regexes),
+        }
+    }
+}
+
+/// Traversal of the files in a part.
+///
+/// A walker is usually obtained from a [`Config`], or from
+/// [`ConfigFile::walker`](crate::config::ConfigFile::walker).
+#[derive(Clone)]
 pub struct Walker {
-    directory: String,
+    matcher: Matcher,
     ignore_hidden: bool,
     use_gitignore: bool,
-    include: regex::bytes::RegexSet,
-    exclude: regex::bytes::RegexSet,
 }
 
 impl From<Config> for Walker {
     fn from(config: Config) -> Self {
-        let directory = config.directory;
         let ignore_hidden = config.ignore_hidden;
         let use_gitignore = config.use_gitignore;
-        let include = merge_globs_and_regexes(config.globs, config.regexes);
-        let exclude = merge_globs_and_regexes(config.exclude_globs, config.exclude_regexes);
+        Walker::new(config.into(), ignore_hidden, use_gitignore)
+    }
+}
+
+impl Walker {
+    pub fn new(matcher: Matcher, ignore_hidden: bool, use_gitignore: bool) -> Self {
         Walker {
-            directory,
+            matcher,
             ignore_hidden,
             use_gitignore,
-            include,
-            exclude,
         }
     }
-}
 
-impl Walker {
-    pub fn walk(&self, buffer_writer: &BufferWriter) {
-        let directory = self.directory.clone();
-        let ignore_hidden = self.ignore_hidden;
-        let use_gitignore = self.use_gitignore;
-        let include = self.include.clone();
-        let exclude = self.exclude.clone();
-        let (tx, rx) = crossbeam_channel::unbounded::<ignore::DirEntry>();
-
-        let walker = ignore::WalkBuilder::new(directory)
-            .hidden(ignore_hidden)
-            .git_ignore(use_gitignore)
-            .build_parallel();
-
-        let stdout_thread = std::thread::spawn({
-            let mut stdout = buffer_writer.buffer();
-            move || {
-                for path_buf in rx.iter().filter_map(|de| {
-                    let path = if de.path().starts_with("./") {
-                        de.path().strip_prefix("./").unwrap()
-                    } else {
-                        de.path()
-                    };
-                    let strl = path.to_string_lossy();
-                    let utf8 = strl.as_bytes();
-                    if path.is_file() && include.is_match(utf8) && !exclude.is_match(utf8) {
-                        Some(path.to_path_buf())
-                    } else {
-                        None
-                    }
-                }) {
-                    write_path(&mut stdout, path_buf.as_path()).unwrap();
+    /// Rules deciding whether a path belongs to the part.
+    pub fn matcher(&self) -> &Matcher {
+        &self.matcher
+    }
+
+    /// Directories in which the part's files are searched.
+    pub fn directories(&self) -> Vec<PathBuf> {
+        self.matcher.directories()
+    }
+
+    /// Whether hidden files are skipped.
+    pub fn ignore_hidden(&self) -> bool {
+        self.ignore_hidden
+    }
+
+    /// Whether files ignored by `.gitignore` rules are skipped.
+    pub fn use_gitignore(&self) -> bool {
+        self.use_gitignore
+    }
+
+    /// Return whether a path, relative to the current directory, is skipped
+    /// as a hidden file during the traversal.
+    ///
+    /// As the directories in which files are searched (see
+    /// [`Walker::directories`]) are never skipped, only the components of
+    /// the path below them are checked, e.g., `.github/workflows/ci.yml` is
+    /// not hidden for a part in `.github/workflows`.
+    pub fn is_hidden(&self, path: &Path) -> bool {
+        if !self.ignore_hidden {
+            return false;
+        }
+
+        let current_dir = current_dir();
+        let path = absolute_from(&current_dir, path);
+
+        self.search_directory(&current_dir, &path)
+            .is_some_and(|directory| is_hidden(path.strip_prefix(directory).unwrap()))
+    }
+
+    /// Return the rule that makes the traversal skip a path, relative to
+    /// the current directory, if gitignore rules are used, given the rules
+    /// that ignore it (see [`Gitignores::rules`]).
+    ///
+    /// As for hidden files (see [`Walker::is_hidden`]), only the rules that
+    /// match the path or its parent directories below the directory it is
+    /// searched in apply. The rule of the shallowest path, where the
+    /// traversal stops, is returned.
+    pub fn gitignore_rule<'a>(
+        &self,
+        path: &Path,
+        rules: &'a [GitignoreRule],
+    ) -> Option<&'a GitignoreRule> {
+        if !self.use_gitignore {
+            return None;
+        }
+
+        let current_dir = current_dir();
+        let directory = self.search_directory(&current_dir, &absolute_from(&current_dir, path))?;
+
+        rules
+            .iter()
+            .rev()
+            .find(|rule| rule.path.starts_with(&directory) && rule.path != directory)
+    }
+
+    /// Return the (absolute) directory in which an absolute path is searched,
+    /// among [`Walker::directories`], if any.
+    fn search_directory(&self, current_dir: &Path, path: &Path) -> Option<PathBuf> {
+        self.matcher
+            .directories_from(current_dir)
+            .iter()
+            .map(|directory| absolute_from(current_dir, directory))
+            .find(|directory| path.starts_with(directory))
+    }
+
+    /// Return whether a path, relative to the current directory, belongs
+    /// to this part.
+    ///
+    /// See [`Matcher::is_match`].
+    pub fn is_match(&self, path: &Path) -> bool {
+        self.matcher.is_match(path)
+    }
+
+    /// Walk through all files in the part, in parallel, and send the
+    /// matching entries on a channel.
+    ///
+    /// Errors encountered during the traversal (e.g., permission denied)
+    /// are logged and skipped.
+    fn run(&self, tx: Sender<Entry>) {
+        let directories: Vec<PathBuf> = self
+            .directories()
+            .into_iter()
+            .map(|directory| {
+                if directory.as_os_str().is_empty() {
+                    PathBuf::from(".")
+                } else {
+                    directory
                 }
-                stdout
-            }
-        });
+            })
+            .collect();
+
+        if directories.is_empty() {
+            return;
+        }
+
+        let walker =
+            walk_builder(&directories, self.ignore_hidden, self.use_gitignore).build_parallel();
+        // Read once, rather than by every thread for every path
+        let current_dir = current_dir();
+        let current_dir = &current_dir;
 
         walker.run(|| {
             let tx = tx.clone();
             Box::new(move |result| {
-                tx.send(result.unwrap()).unwrap();
+                let de = match result {
+                    Ok(de) => de,
+                    Err(err) => {
+                        warn!("{err}");
+                        return ignore::WalkState::Continue;
+                    }
+                };
+                let path = de.path().strip_prefix("./").unwrap_or_else(|_| de.path());
+
+                if !self
+                    .matcher
+                    .is_match_absolute(&absolute_from(current_dir, path))
+                {
+                    return ignore::WalkState::Continue;
+                }
+
+                match std::fs::metadata(path) {
+                    Ok(metadata) if metadata.is_file() => {
+                        let entry = Entry {
+                            path: path.to_path_buf(),
+                            depth: de.depth(),
+                            metadata,
+                        };
+                        if tx.send(entry).is_err() {
+                            // The receiver was dropped, no need to continue
+                            return ignore::WalkState::Quit;
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(err) => warn!("{}: {err}", path.display()),
+                }
                 ignore::WalkState::Continue
             })
         });
+    }
 
-        drop(tx);
-        buffer_writer.print(&stdout_thread.join().unwrap()).unwrap();
+    /// Return an iterator over all files in the part.
+    ///
+    /// The traversal is performed in parallel, in a background thread,
+    /// and entries are yielded as soon as they are found, in a
+    /// non-deterministic order.
+    pub fn iter(&self) -> impl Iterator<Item = Entry> {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let walker = self.clone();
+        std::thread::spawn(move || walker.run(tx));
+        rx.into_iter()
     }
+
+    /// Call a function on every file in the part, in a non-deterministic order.
+    pub fn for_each<F: FnMut(Entry)>(&self, f: F) {
+        self.iter().for_each(f)
+    }
+
+    /// Walk through all files in the part, and return their entries,
+    /// sorted by path.
+    pub fn entries(&self) -> Vec<Entry> {
+        let mut entries: Vec<Entry> = self.iter().collect();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        entries
+    }
+
+    /// Walk through all files in the part, and return their paths, sorted.
+    pub fn collect(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = self.iter().map(|entry| entry.path).collect();
+        paths.sort();
+        paths
+    }
+
+    /// Walk through all files in the part, and print their paths.
+    ///
+    /// If `sorted` is true, paths are printed in alphabetical order, once
+    /// the traversal is complete. Otherwise, they are printed in the order
+    /// they are found.
+    ///
+    /// Paths are relative to the current directory, or to `base` if it is
+    /// given (e.g., the root of the config file).
+    pub fn walk(
+        &self,
+        buffer_writer: &BufferWriter,
+        sorted: bool,
+        base: Option<&Path>,
+    ) -> std::io::Result<()> {
+        let mut stdout = buffer_writer.buffer();
+        let current_dir = current_dir();
+        let base = base.map(|base| absolute_from(&current_dir, base));
+        let rebase = |path: &Path| match &base {
+            Some(base) => relative_to(&absolute_from(&current_dir, path), base),
+            None => path.to_path_buf(),
+        };
+
+        if sorted {
+            let paths: Vec<PathBuf> = self
+                .collect()
+                .iter()
+                .map(|path| rebase(path))
+                .sorted()
+                .collect();
+
+            for path in paths {
+                write_path(&mut stdout, &path)?;
+            }
+        } else {
+            for entry in self.iter() {
+                write_path(&mut stdout, &rebase(&entry.path))?;
+            }
+        }
+
+        buffer_writer.print(&stdout)
+    }
+}
+
+/// Walk through the files of several parts at once, and return, for each
+/// file, the indices of the walkers it belongs to.
+///
+/// Walkers with the same hidden and ignored files settings share a single
+/// traversal, so the tree is walked only once in the common case.
+pub fn classify(walkers: &[Walker]) -> BTreeMap<PathBuf, Vec<usize>> {
+    let mut files: BTreeMap<PathBuf, Vec<usize>> = BTreeMap::new();
+    let current_dir = current_dir();
+    let groups = walkers
+        .iter()
+        .enumerate()
+        .into_group_map_by(|(_, walker)| (walker.ignore_hidden, walker.use_gitignore));
+
+    for ((ignore_hidden, use_gitignore), group) in groups {
+        let matcher = Matcher::Any(
+            group
+                .iter()
+                .map(|(_, walker)| walker.matcher.clone())
+                .collect(),
+        );
+
+        for entry in Walker::new(matcher, ignore_hidden, use_gitignore).iter() {
+            let path = absolute_from(&current_dir, &entry.path);
+            let indices: Vec<usize> = group
+                .iter()
+                .filter(|(_, walker)| walker.matcher.is_match_absolute(&path))
+                .map(|(index, _)| *index)
+                .collect();
+
+            files.entry(entry.path).or_default().extend(indices);
+        }
+    }
+
+    for indices in files.values_mut() {
+        indices.sort_unstable();
+    }
+
+    files
 }